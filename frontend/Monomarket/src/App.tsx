@@ -6,6 +6,7 @@ import type {
   ClientMessage,
   GameEnded,
   InitialState,
+  LeaderboardEntry,
   LogEntry,
   NeedsToRegister,
   PricePoint,
@@ -26,6 +27,23 @@ const WS_URL = (() => {
   return `${protocol}//${window.location.host}/ws`;
 })();
 const CHAIN_ID = 10143n;
+
+// Finds a solution whose keccak256(challenge ‖ address ‖ solution) starts with
+// `difficulty` zero bits, matching faucet::verify_pow on the server.
+const solveFundChallenge = (
+  challenge: string,
+  address: string,
+  difficulty: number
+): number => {
+  const target = 1n << BigInt(256 - difficulty);
+  for (let solution = 0; ; solution++) {
+    const hash = ethers.solidityPackedKeccak256(
+      ["bytes32", "address", "uint64"],
+      [challenge, address, solution]
+    );
+    if (BigInt(hash) < target) return solution;
+  }
+};
 const GAS_PRICE = 0x21d664903cn;
 
 const CONTRACT_ABI = [
@@ -51,6 +69,9 @@ function App() {
     Map<string, Portfolio>
   >(new Map());
   const [names, setNames] = useState<Map<string, string>>(new Map());
+  const [leaderboard, setLeaderboard] = useState<LeaderboardEntry[]>([]);
+  // Prices the server had before we connected, used to seed the chart.
+  const serverPriceHistoryRef = useRef<PricePoint[]>([]);
   const [floatingMessages, setFloatingMessages] = useState<
     Array<{ id: number; x: number; y: number; message: string }>
   >([]);
@@ -60,6 +81,7 @@ function App() {
   const consoleRef = useRef<HTMLDivElement>(null);
   const prevHoldingsRef = useRef<Map<string, number>>(new Map());
   const messageIdRef = useRef(0);
+  const sessionRef = useRef<string | null>(null);
   const namesRef = useRef<Map<string, string>>(new Map());
   const stateNameRef = useRef<State["name"]>("InitialState");
 
@@ -76,7 +98,11 @@ function App() {
   const isAdmin = () => {
     return document.cookie
       .split(";")
-      .some((item) => item.trim().startsWith("ADMIN=1"));
+      .some(
+        (item) =>
+          item.trim().startsWith("ADMIN=1") ||
+          item.trim().startsWith("ADMIN_TOKEN=")
+      );
   };
 
   const handleRestartGame = () => {
@@ -115,18 +141,47 @@ function App() {
       console.log("Server message:", data);
 
       switch (data.type) {
+        case "fund_queued": {
+          console.log(`Funding queued for ${data.address}`);
+          break;
+        }
+
+        case "fund_pending": {
+          console.log(`Funding tx sent: ${data.tx_hash}`);
+          break;
+        }
+
         case "funded": {
           console.log(`Funded: ${data.amount} wei`);
           setState((prev) => {
             if (prev.name === "InitialState") return prev;
             return {
               ...prev,
-              state: { ...prev.state, funds: data.amount },
+              state: { ...prev.state, funds: Number(data.amount) },
             } as State;
           });
           break;
         }
 
+        case "fund_error": {
+          addLog(`Funding refused: ${data.error}`, "error");
+          break;
+        }
+
+        case "fund_challenge": {
+          const solution = solveFundChallenge(
+            data.challenge,
+            data.address,
+            data.difficulty
+          );
+          sendMessage({
+            type: "solve_challenge",
+            address: data.address,
+            solution,
+          });
+          break;
+        }
+
         case "nonce_response": {
           console.log(`Nonce received: ${data.nonce}`);
           setState((prev) => {
@@ -141,6 +196,19 @@ function App() {
 
         case "connection_info": {
           console.log(`Connected to contract: ${data.contract_address}`);
+          sessionRef.current = data.session;
+          // Must match ownership::funding_message on the server.
+          loadedWallet
+            .signMessage(
+              `Monomarket: request funds for ${loadedWallet.address}\nSession: ${data.session}`
+            )
+            .then((signature) =>
+              sendMessage({
+                type: "request_funds",
+                address: loadedWallet.address,
+                signature,
+              })
+            );
           console.log(
             `Gas costs - register: ${data.gas_costs.register}, buy: ${data.gas_costs.buy}, sell: ${data.gas_costs.sell}`
           );
@@ -164,6 +232,20 @@ function App() {
           break;
         }
 
+        case "gas_costs": {
+          console.log(
+            `Gas costs updated - register: ${data.gas_costs.register}, buy: ${data.gas_costs.buy}, sell: ${data.gas_costs.sell}`
+          );
+          setState((prev) => {
+            if (prev.name === "InitialState") return prev;
+            return {
+              ...prev,
+              state: { ...prev.state, gasCosts: data.gas_costs },
+            } as State;
+          });
+          break;
+        }
+
         case "price_update": {
           setState((prev) => {
             switch (prev.name) {
@@ -264,6 +346,11 @@ function App() {
           break;
         }
 
+        case "name_error": {
+          addLog(`Name "${data.name}" rejected: ${data.error}`, "error");
+          break;
+        }
+
         case "position": {
           const addressLower = data.address.toLowerCase();
           const previousHoldings = prevHoldingsRef.current.get(addressLower);
@@ -348,6 +435,73 @@ function App() {
           break;
         }
 
+        case "tx_confirmed": {
+          console.log(
+            `TX confirmed: ${data.tx_hash} (block ${data.block_number}, gas ${data.gas_used})`
+          );
+          break;
+        }
+
+        case "tx_reverted": {
+          addLog(`TX reverted: ${data.reason}`, "error");
+          break;
+        }
+
+        case "tx_dropped": {
+          addLog(`TX dropped: ${data.tx_hash}`, "error");
+          break;
+        }
+
+        case "price_history": {
+          const points: PricePoint[] = data.points.map((point) => ({
+            blockNumber: point.block_number,
+            price: point.price,
+            timestamp: new Date(),
+          }));
+          serverPriceHistoryRef.current = points;
+          setState((prev) => {
+            if (prev.name !== "TradableState") return prev;
+            const newer = prev.state.priceHistory.filter(
+              (point) =>
+                point.blockNumber > (points[points.length - 1]?.blockNumber ?? -1)
+            );
+            return {
+              ...prev,
+              state: {
+                ...prev.state,
+                priceHistory: [...points, ...newer].slice(-200),
+              },
+            } satisfies TradableState;
+          });
+          break;
+        }
+
+        case "leaderboard": {
+          setLeaderboard(data.entries);
+          break;
+        }
+
+        case "final_standings": {
+          setLeaderboard(data.entries);
+          const winner = data.entries[0];
+          if (winner) {
+            addLog(`Winner: ${winner.name ?? winner.address}`, "info");
+          }
+          break;
+        }
+
+        case "trades": {
+          console.log(
+            `${data.trades.length} trades for ${data.address}, P&L ${data.pnl}`
+          );
+          break;
+        }
+
+        case "unauthorized": {
+          addLog(`${data.action} refused: ${data.error}`, "error");
+          break;
+        }
+
         case "game_started": {
           console.log(
             `Game started: blocks ${data.start_height} to ${data.end_height}, state = ${stateNameRef.current}`
@@ -458,6 +612,10 @@ function App() {
         price: state.state.currentPrice,
         timestamp: new Date(),
       };
+      const startHeight = state.state.startHeight;
+      const knownHistory = serverPriceHistoryRef.current.filter(
+        (point) => point.blockNumber >= startHeight
+      );
       setState({
         name: "TradableState",
         state: {
@@ -465,7 +623,10 @@ function App() {
           startHeight: state.state.startHeight,
           endHeight: state.state.endHeight,
           currentBlockHeight: state.state.currentBlockHeight,
-          priceHistory: [initialPricePoint],
+          priceHistory:
+            knownHistory.length > 0
+              ? knownHistory.slice(-200)
+              : [initialPricePoint],
           firstBlockTimestamp: undefined,
         },
       } satisfies TradableState);
@@ -482,10 +643,19 @@ function App() {
     const name = nameInput.trim();
     console.log(`Setting name: ${name}`);
 
+    if (sessionRef.current === null) {
+      addLog("Not connected to the server yet", "error");
+      return;
+    }
+    // Must match ownership::name_message on the server.
+    const signature = await state.state.wallet.signMessage(
+      `Monomarket: set name for ${state.state.wallet.address} to "${name}"\nSession: ${sessionRef.current}`
+    );
     sendMessage({
       type: "set_name",
       name: name,
       address: state.state.wallet.address,
+      signature,
     });
 
     const { wallet, funds, contract, gasCosts, nonce, balance, holdings } =
@@ -673,6 +843,10 @@ function App() {
           <p className="winner-text">
             Winner:{" "}
             {(() => {
+              if (leaderboard.length > 0) {
+                const winner = leaderboard[0];
+                return winner.name ?? names.get(winner.address) ?? "Unknown";
+              }
               const sorted = Array.from(currentPortfolio.entries())
                 .map(([address, portfolio]) => {
                   const price = state.state.currentPrice;
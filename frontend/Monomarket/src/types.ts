@@ -6,10 +6,83 @@ export interface GasInfo {
   sell: number;
 }
 
+export type TxErrorReason =
+  | "malformed"
+  | "wrong_chain_id"
+  | "wrong_recipient"
+  | "unknown_selector"
+  | "invalid_signature"
+  | "insufficient_balance"
+  | "rejected"
+  | "rpc";
+
+export type NameErrorReason =
+  | "invalid_signature"
+  | "invalid_length"
+  | "invalid_characters"
+  | "taken"
+  | "rejected";
+
+export interface Candle {
+  window: number;
+  start_block: number;
+  end_block: number;
+  open: number;
+  high: number;
+  low: number;
+  close: number;
+  volume: number;
+}
+
+export interface Trade {
+  address: string;
+  side: "buy" | "sell";
+  quantity: number;
+  price: number;
+  block_number: number;
+  tx_hash: string;
+}
+
+export interface LeaderboardEntry {
+  address: string;
+  name: string | null;
+  net_worth: number;
+  rank: number;
+}
+
+export type FundErrorReason =
+  | "invalid_signature"
+  | "already_funded"
+  | "connection_limit"
+  | "ip_limit"
+  | "budget_exhausted"
+  | "challenge_failed"
+  | "tx_failed"
+  | "unavailable";
+
 export type ServerMessage =
-  | { type: "funded"; address: string; amount: number }
-  | { type: "fund_error"; address: string; error: string }
-  | { type: "connection_info"; contract_address: string; gas_costs: GasInfo }
+  | { type: "fund_queued"; address: string }
+  | { type: "fund_pending"; address: string; tx_hash: string }
+  | { type: "funded"; address: string; amount: string }
+  | {
+      type: "fund_error";
+      address: string;
+      error: string;
+      reason: FundErrorReason;
+    }
+  | {
+      type: "fund_challenge";
+      address: string;
+      challenge: string;
+      difficulty: number;
+    }
+  | {
+      type: "connection_info";
+      contract_address: string;
+      session: string;
+      gas_costs: GasInfo;
+    }
+  | { type: "gas_costs"; gas_costs: GasInfo }
   | { type: "nonce_response"; address: string; nonce: number }
   | {
       type: "price_update";
@@ -17,8 +90,16 @@ export type ServerMessage =
       block_number: number;
     }
   | { type: "current_price"; price: number }
+  | { type: "price_history"; points: { block_number: number; price: number }[] }
   | { type: "current_block_height"; height: number }
   | { type: "name_set"; address: string; name: string }
+  | {
+      type: "name_error";
+      address: string;
+      name: string;
+      error: string;
+      reason: NameErrorReason;
+    }
   | {
       type: "position";
       address: string;
@@ -26,15 +107,32 @@ export type ServerMessage =
       holdings: number;
       block_number: number;
     }
-  | { type: "tx_error"; error: string }
+  | { type: "tx_error"; error: string; reason: TxErrorReason }
   | { type: "tx_submitted"; tx_hash: string }
+  | {
+      type: "tx_confirmed";
+      tx_hash: string;
+      block_number: number;
+      gas_used: number;
+    }
+  | { type: "tx_reverted"; tx_hash: string; reason: string }
+  | { type: "tx_dropped"; tx_hash: string }
+  | { type: "unauthorized"; action: string; error: string }
   | { type: "game_started"; start_height: number; end_height: number }
-  | { type: "game_ended" };
+  | { type: "game_ended" }
+  | { type: "leaderboard"; entries: LeaderboardEntry[] }
+  | { type: "candle_closed"; candle: Candle }
+  | { type: "trades"; address: string; trades: Trade[]; pnl: number }
+  | { type: "final_standings"; entries: LeaderboardEntry[] };
 
 export type ClientMessage =
-  | { type: "set_name"; name: string; address: string }
+  | { type: "set_name"; name: string; address: string; signature: string }
   | { type: "raw_tx"; raw_tx: string }
   | { type: "get_nonce"; address: string }
+  | { type: "request_funds"; address: string; signature: string }
+  | { type: "solve_challenge"; address: string; solution: number }
+  | { type: "get_price_history"; from_block: number; to_block: number }
+  | { type: "get_trades"; address: string }
   | { type: "restart_game" };
 
 export type AppStatus = "disconnected" | "connected" | "funded";
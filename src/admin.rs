@@ -0,0 +1,211 @@
+use crate::{ServerState, config::AdminConfig, store::Store};
+use alloy::{
+    providers::{Provider, WalletProvider},
+    transports::Transport,
+};
+use axum::{
+    extract::{ConnectInfo, State as AxumState},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::{
+    net::SocketAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const TOKEN_COOKIE: &str = "ADMIN_TOKEN";
+
+/// Checks the admin token presented when opening /ws. Browsers can't set
+/// headers on a WebSocket, so besides `Authorization: Bearer` we also accept
+/// an `ADMIN_TOKEN` cookie, but only from an allowed `Origin`: browsers send
+/// cookies on cross-site WebSocket upgrades too, so otherwise any page the
+/// admin visits could open an admin connection.
+pub fn is_admin(headers: &HeaderMap, config: &AdminConfig) -> bool {
+    let Some(expected) = config.token.as_deref() else {
+        return false;
+    };
+
+    let cookie = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == TOKEN_COOKIE)
+        .map(|(_, value)| value)
+        .filter(|_| origin_allowed(headers, &config.allowed_origins));
+
+    [bearer(headers), cookie]
+        .into_iter()
+        .flatten()
+        .any(|token| constant_time_eq(token.as_bytes(), expected.as_bytes()))
+}
+
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Same origin as the `Host` the request was sent to, or listed in
+/// `admin.allowed_origins`.
+fn origin_allowed(headers: &HeaderMap, allowed: &[String]) -> bool {
+    let Some(origin) = headers
+        .get(header::ORIGIN)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    if allowed.iter().any(|allowed| allowed == origin) {
+        return true;
+    }
+    let host = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok());
+    origin
+        .split_once("://")
+        .is_some_and(|(_, origin_host)| Some(origin_host) == host)
+}
+
+/// `POST /admin/session`: trades `Authorization: Bearer <token>` for the
+/// admin cookies. The token cookie is HttpOnly and SameSite=Strict; `ADMIN=1`
+/// only tells the frontend to show the admin controls.
+pub(crate) async fn login<T, P>(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    AxumState(state): AxumState<ServerState<T, P>>,
+) -> Response
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    let token = state.config.admin.token.as_deref();
+    let authorized = token.is_some_and(|expected| {
+        bearer(&headers)
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes()))
+    });
+    // Anyone can POST here, so failures only go to the log instead of
+    // growing the audit tree.
+    let (true, Some(token)) = (authorized, token) else {
+        tracing::warn!(target: "audit", "🚫 Unauthorized admin_login from {}", peer);
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    audit(
+        &state.app_state.read().await.store,
+        peer,
+        "admin_login",
+        true,
+    );
+
+    let Ok(token_cookie) = HeaderValue::from_str(&format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Strict",
+        TOKEN_COOKIE, token
+    )) else {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "admin.token is not a valid cookie value",
+        )
+            .into_response();
+    };
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    let cookies = response.headers_mut();
+    cookies.append(header::SET_COOKIE, token_cookie);
+    cookies.append(
+        header::SET_COOKIE,
+        HeaderValue::from_static("ADMIN=1; Path=/; SameSite=Strict"),
+    );
+    response
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Debug, Serialize)]
+struct AuditEntry<'a> {
+    timestamp: u64,
+    peer: SocketAddr,
+    action: &'a str,
+    authorized: bool,
+}
+
+/// Records an admin command attempt, allowed or not, in the log and the store.
+pub fn audit(store: &Store, peer: SocketAddr, action: &str, authorized: bool) {
+    if authorized {
+        tracing::info!(target: "audit", "🔑 {} requested {}", peer, action);
+    } else {
+        tracing::warn!(target: "audit", "🚫 Unauthorized {} from {}", action, peer);
+    }
+
+    let entry = AuditEntry {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        peer,
+        action,
+        authorized,
+    };
+    if let Err(e) = store.append_audit(&entry) {
+        tracing::error!("Failed to persist audit entry: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    fn config() -> AdminConfig {
+        AdminConfig {
+            token: Some(TOKEN.to_string()),
+            allowed_origins: vec!["https://admin.example".to_string()],
+        }
+    }
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn cookie_needs_an_allowed_origin() {
+        let cookie = format!("{}={}", TOKEN_COOKIE, TOKEN);
+        let from = |origin: &str| {
+            headers(&[
+                (header::HOST, "market.example"),
+                (header::ORIGIN, origin),
+                (header::COOKIE, cookie.as_str()),
+            ])
+        };
+
+        assert!(is_admin(&from("https://market.example"), &config()));
+        assert!(is_admin(&from("https://admin.example"), &config()));
+        assert!(!is_admin(&from("https://evil.example"), &config()));
+        assert!(!is_admin(
+            &headers(&[(header::HOST, "market.example"), (header::COOKIE, &cookie)]),
+            &config()
+        ));
+    }
+
+    #[test]
+    fn bearer_works_without_origin() {
+        let auth = format!("Bearer {}", TOKEN);
+        assert!(is_admin(
+            &headers(&[(header::AUTHORIZATION, &auth)]),
+            &config()
+        ));
+        assert!(!is_admin(
+            &headers(&[(header::AUTHORIZATION, "Bearer nope")]),
+            &config()
+        ));
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{
+    AppState, ServerState,
+    archive::{GameRecord, GameSummary},
+    leaderboard, trades,
+    ws::{Candle, GasInfo, Trade},
+};
+use alloy::{
+    primitives::Address,
+    providers::{Provider, WalletProvider},
+    transports::Transport,
+};
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State as AxumState},
+    http::StatusCode,
+    routing::get,
+};
+use serde::{Deserialize, Serialize};
+
+type ApiResult<T> = Result<Json<T>, (StatusCode, String)>;
+
+/// Read-only JSON endpoints, mounted under `/api`.
+pub(crate) fn routes<T, P>() -> Router<ServerState<T, P>>
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    Router::new()
+        .route("/state", get(get_state))
+        .route("/players", get(list_players))
+        .route("/players/:address", get(get_player))
+        .route("/players/:address/trades", get(get_trades))
+        .route("/gas", get(get_gas))
+        .route("/games", get(list_games))
+        .route("/games/:id", get(get_game))
+        .route("/candles", get(get_candles))
+}
+
+fn internal_error(e: anyhow::Error) -> (StatusCode, String) {
+    tracing::error!("API error: {}", e);
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+#[derive(Debug, Serialize)]
+struct GameState {
+    price: u64,
+    block: u64,
+    start_block: Option<u64>,
+    end_block: Option<u64>,
+    /// Trading is open at `block`
+    active: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Player {
+    address: String,
+    name: Option<String>,
+    balance: u64,
+    holdings: u64,
+    net_worth: u64,
+    rank: u32,
+}
+
+async fn get_state<T, P>(AxumState(state): AxumState<ServerState<T, P>>) -> Json<GameState>
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    let state_guard = state.app_state.read().await;
+    let block = state_guard.current_block_height;
+    Json(GameState {
+        price: state_guard.current_price,
+        block,
+        start_block: state_guard.game_start_block,
+        end_block: state_guard.game_end_block,
+        active: matches!(
+            (state_guard.game_start_block, state_guard.game_end_block),
+            (Some(start), Some(end)) if (start..=end).contains(&block)
+        ),
+    })
+}
+
+/// Every player with a position, best net worth first.
+fn players(state: &AppState) -> Vec<Player> {
+    leaderboard::ranked(state)
+        .into_iter()
+        .map(|(address, entry)| Player {
+            balance: state.balances.get(&address).copied().unwrap_or(0),
+            holdings: state.holdings.get(&address).copied().unwrap_or(0),
+            address: entry.address,
+            name: entry.name,
+            net_worth: entry.net_worth,
+            rank: entry.rank,
+        })
+        .collect()
+}
+
+async fn list_players<T, P>(AxumState(state): AxumState<ServerState<T, P>>) -> Json<Vec<Player>>
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    Json(players(&*state.app_state.read().await))
+}
+
+async fn get_player<T, P>(
+    AxumState(state): AxumState<ServerState<T, P>>,
+    Path(address): Path<String>,
+) -> ApiResult<Player>
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    let address: Address = address
+        .parse()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid address: {}", e)))?;
+    let formatted = format!("{:?}", address);
+    players(&*state.app_state.read().await)
+        .into_iter()
+        .find(|player| player.address == formatted)
+        .map(Json)
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("Unknown player {}", formatted),
+            )
+        })
+}
+
+#[derive(Debug, Serialize)]
+struct TradeHistory {
+    address: String,
+    trades: Vec<Trade>,
+    /// Marked to the current price
+    pnl: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TradeQuery {
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+}
+
+/// Trades between the given blocks, defaulting to the current game's window.
+async fn get_trades<T, P>(
+    AxumState(state): AxumState<ServerState<T, P>>,
+    Path(address): Path<String>,
+    Query(query): Query<TradeQuery>,
+) -> ApiResult<TradeHistory>
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    let address: Address = address
+        .parse()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid address: {}", e)))?;
+    let state_guard = state.app_state.read().await;
+    let from_block = query
+        .from_block
+        .unwrap_or(state_guard.game_start_block.unwrap_or(0));
+    let to_block = query
+        .to_block
+        .unwrap_or(state_guard.game_end_block.unwrap_or(u64::MAX));
+    let (trades, pnl) =
+        trades::between(&state_guard, address, from_block, to_block).map_err(internal_error)?;
+    Ok(Json(TradeHistory {
+        address: format!("{:?}", address),
+        trades,
+        pnl,
+    }))
+}
+
+async fn get_gas<T, P>(AxumState(state): AxumState<ServerState<T, P>>) -> Json<GasInfo>
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    let gas_costs = state.gas_costs.read().await;
+    Json(GasInfo {
+        register: gas_costs.register,
+        buy: gas_costs.buy,
+        sell: gas_costs.sell,
+    })
+}
+
+async fn list_games<T, P>(
+    AxumState(state): AxumState<ServerState<T, P>>,
+) -> ApiResult<Vec<GameSummary>>
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    let state_guard = state.app_state.read().await;
+    let games: Vec<GameRecord> = state_guard.store.load_games().map_err(internal_error)?;
+    Ok(Json(games.iter().map(GameSummary::from).collect()))
+}
+
+async fn get_game<T, P>(
+    AxumState(state): AxumState<ServerState<T, P>>,
+    Path(id): Path<u64>,
+) -> ApiResult<GameRecord>
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    let state_guard = state.app_state.read().await;
+    match state_guard.store.load_game(id).map_err(internal_error)? {
+        Some(record) => Ok(Json(record)),
+        None => Err((StatusCode::NOT_FOUND, format!("No game with id {}", id))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CandleQuery {
+    window: u64,
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+}
+
+async fn get_candles<T, P>(
+    AxumState(state): AxumState<ServerState<T, P>>,
+    Query(query): Query<CandleQuery>,
+) -> ApiResult<Vec<Candle>>
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    let state_guard = state.app_state.read().await;
+    if !state_guard.candles.tracks(query.window) {
+        let windows: Vec<String> = state_guard
+            .candles
+            .windows()
+            .map(|w| w.to_string())
+            .collect();
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!(
+                "No {}-block candles, available windows: {}",
+                query.window,
+                windows.join(", ")
+            ),
+        ));
+    }
+
+    // Closed candles come from the store, the open one is still in memory.
+    let (from, to) = (
+        query.from_block.unwrap_or(0),
+        query.to_block.unwrap_or(u64::MAX),
+    );
+    let mut candles: Vec<Candle> = state_guard
+        .store
+        .load_candles(query.window, from, to)
+        .map_err(internal_error)?;
+    candles.extend(
+        state_guard
+            .candles
+            .open_candle(query.window)
+            .filter(|candle| (from..=to).contains(&candle.start_block))
+            .cloned(),
+    );
+    Ok(Json(candles))
+}
@@ -0,0 +1,91 @@
+use crate::{
+    AppState, leaderboard,
+    ws::{LeaderboardEntry, PricePoint, Trade},
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::RwLock;
+
+/// Everything worth keeping about a finished game. Games are identified by
+/// their start block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub id: u64,
+    pub start_block: u64,
+    pub end_block: u64,
+    /// Unix seconds when the record was written
+    pub archived_at: u64,
+    pub prices: Vec<PricePoint>,
+    pub leaderboard: Vec<LeaderboardEntry>,
+    pub trade_counts: BTreeMap<String, u32>,
+}
+
+/// `GameRecord` without the bulky parts, for listings.
+#[derive(Debug, Clone, Serialize)]
+pub struct GameSummary {
+    pub id: u64,
+    pub start_block: u64,
+    pub end_block: u64,
+    pub archived_at: u64,
+    pub players: usize,
+    pub trades: u32,
+    pub winner: Option<LeaderboardEntry>,
+}
+
+impl From<&GameRecord> for GameSummary {
+    fn from(record: &GameRecord) -> Self {
+        Self {
+            id: record.id,
+            start_block: record.start_block,
+            end_block: record.end_block,
+            archived_at: record.archived_at,
+            players: record.leaderboard.len(),
+            trades: record.trade_counts.values().sum(),
+            winner: record.leaderboard.first().cloned(),
+        }
+    }
+}
+
+/// Writes the current game to the archive. Returns `None` if no game has been
+/// played or it was already archived (e.g. before a server restart).
+pub async fn archive_game(state: &Arc<RwLock<AppState>>) -> Result<Option<GameRecord>> {
+    let state_guard = state.read().await;
+    let (Some(start_block), Some(end_block)) =
+        (state_guard.game_start_block, state_guard.game_end_block)
+    else {
+        return Ok(None);
+    };
+    let store = &state_guard.store;
+    if store.load_game::<GameRecord>(start_block)?.is_some() {
+        return Ok(None);
+    }
+
+    let mut record = GameRecord {
+        id: start_block,
+        start_block,
+        end_block,
+        archived_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        prices: store
+            .load_prices(0, u64::MAX)?
+            .into_iter()
+            .map(|(block_number, price)| PricePoint {
+                block_number,
+                price,
+            })
+            .collect(),
+        leaderboard: leaderboard::compute(&state_guard),
+        trade_counts: BTreeMap::new(),
+    };
+    for trade in store.load_all_trades::<Trade>()? {
+        if (start_block..=end_block).contains(&trade.block_number) {
+            *record.trade_counts.entry(trade.address).or_default() += 1;
+        }
+    }
+    store.insert_game(record.id, &record)?;
+    Ok(Some(record))
+}
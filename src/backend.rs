@@ -1,15 +1,15 @@
-use crate::ws::ServerMessage;
+use crate::chain::{MarketChain, Receipt};
+use crate::config::Config;
+use crate::faucet::Faucet;
+use crate::metrics::METRICS;
+use crate::ws::{FundErrorReason, ServerMessage};
 use crate::{AppState, BackendTxEvent};
-use alloy::{
-    network::TransactionBuilder,
-    primitives::{Address, U256},
-    providers::{Provider, WalletProvider},
-    rpc::types::TransactionRequest,
-    transports::Transport,
-};
+use alloy::primitives::{Address, U256};
 use anyhow::Result;
-use std::sync::Arc;
+use futures_util::future::join_all;
+use std::{net::IpAddr, sync::Arc};
 use tokio::sync::{RwLock, broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
 
 mod contract {
     use alloy::sol;
@@ -23,40 +23,53 @@ mod contract {
 
 pub use contract::StockMarket;
 
-async fn handle_fund_event<'a, T, P>(
-    provider: &P,
-    contract: &StockMarket::StockMarketInstance<T, &'a P>,
+/// Shared handles the backend needs besides the chain: the faucet policy and
+/// the static limits from the config.
+#[derive(Clone)]
+pub struct BackendContext {
+    pub faucet: Arc<Faucet>,
+    pub config: Arc<Config>,
+}
+
+/// A mined (or failed) funding tx, reported back to the executor by the task
+/// that waited for it.
+struct FundingOutcome {
+    addr: Address,
+    client_tx: mpsc::Sender<ServerMessage>,
+    receipt: Result<Receipt>,
+}
+
+/// Sends the funding tx and hands the receipt wait to a separate task, so the
+/// executor can move on to the next event straight away.
+async fn handle_fund_event<C: MarketChain>(
+    chain: &C,
     addr: Address,
+    ip: IpAddr,
     broadcast_tx: &broadcast::Sender<ServerMessage>,
     client_tx: &mpsc::Sender<ServerMessage>,
-    state: Arc<RwLock<AppState>>,
-) -> Result<()>
-where
-    T: Transport + Clone,
-    P: Provider<T> + WalletProvider,
-{
-    let balance = provider.get_balance(addr).await?;
+    outcome_tx: &mpsc::Sender<FundingOutcome>,
+    ctx: &BackendContext,
+) -> Result<()> {
+    let balance = chain.get_balance(addr).await?;
     tracing::info!("Balance for {:?}: {} wei", addr, balance);
 
     if balance > U256::ZERO {
         tracing::info!(
             "Address already funded, reading holdings and sending Funded and Position events"
         );
-        let holdings = contract.getHoldings(addr).call().await?;
-        let contract_balance = contract.getBalance(addr).call().await?;
+        let holdings = chain.get_holdings(addr).await?;
+        let contract_balance = chain.get_credits(addr).await?;
 
         let funded_msg = ServerMessage::Funded {
             address: format!("{:?}", addr),
-            amount: balance.to::<u64>(),
+            amount: balance.to_string(),
         };
         let _ = client_tx.send(funded_msg).await;
 
-        let balance = contract_balance._0.to::<u64>();
-        let holdings = holdings._0.to::<u64>();
-        if balance > 0 && holdings > 0 {
+        if contract_balance > 0 && holdings > 0 {
             let position_msg = ServerMessage::Position {
                 address: format!("{:?}", addr),
-                balance,
+                balance: contract_balance,
                 holdings,
                 block_number: 0,
             };
@@ -66,125 +79,181 @@ where
     }
 
     tracing::info!("Balance is zero, funding account...");
-    let funding_amount = U256::from(500_000_000_000_000_000u64);
-    tracing::info!("Funding {:?} with {} wei (0.5 MON)", addr, funding_amount);
-
-    let nonce = {
-        let mut state_guard = state.write().await;
-        let nonce = state_guard.backend_nonce;
-        state_guard.backend_nonce += 1;
-        nonce
-    };
+    if let Err(refusal) = ctx
+        .faucet
+        .authorize(addr, ip, ctx.config.faucet.amount)
+        .await
+    {
+        tracing::warn!(
+            "🚫 Refused to fund {:?} from {} ({:?}): {}",
+            addr,
+            ip,
+            refusal.reason,
+            refusal.message
+        );
+        let msg = ServerMessage::FundError {
+            address: format!("{:?}", addr),
+            error: refusal.message,
+            reason: refusal.reason,
+        };
+        let _ = client_tx.send(msg).await;
+        return Ok(());
+    }
 
-    let gas_price = U256::from(0x21d664903cu64);
-    let gas_limit = 25_000u64; // experimentally obtained 25k gas
-    tracing::info!(
-        "Gas cost for funding tx: {} wei",
-        gas_price * U256::from(gas_limit)
-    );
+    let funding_amount = ctx.config.faucet.amount;
+    tracing::info!("Funding {:?} with {} wei", addr, funding_amount);
 
-    let tx = TransactionRequest::default()
-        .to(addr)
-        .value(funding_amount)
-        .with_nonce(nonce)
-        .with_gas_limit(gas_limit)
-        .with_gas_price(gas_price.to::<u128>());
-
-    let pending = provider.send_transaction(tx).await?;
-    let tx_hash = *pending.tx_hash();
-    tracing::info!("📤 Funding tx sent: {:?} (nonce: {})", tx_hash, nonce);
-
-    let receipt = loop {
-        match provider.get_transaction_receipt(tx_hash).await? {
-            Some(receipt) => break receipt,
-            None => {
-                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-            }
+    let sent = match chain.send_transaction(addr, funding_amount).await {
+        Ok(sent) => sent,
+        Err(e) => {
+            ctx.faucet.release(addr).await;
+            return Err(e);
         }
     };
+    METRICS.funds_sent.inc();
     tracing::info!(
-        "✅ Funding tx confirmed: {:?} (block: {}, status: {})",
-        tx_hash,
-        receipt.block_number.unwrap_or_default(),
-        if receipt.status() {
-            "success"
-        } else {
-            "failed"
-        }
+        "📤 Funding tx sent: {:?} (nonce: {})",
+        sent.tx_hash,
+        sent.nonce
     );
-
-    if receipt.status() {
-        let funded_msg = ServerMessage::Funded {
-            address: format!("{:?}", addr),
-            amount: funding_amount.to::<u64>(),
-        };
-        let _ = client_tx.send(funded_msg).await;
-    } else {
-        let error_msg = format!("Funding transaction failed: {:?}", tx_hash);
-        tracing::error!("{}", error_msg);
-        let msg = ServerMessage::FundError {
-            address: format!("{:?}", addr),
-            error: error_msg,
-        };
-        let _ = client_tx.send(msg).await;
-    }
+    let pending_msg = ServerMessage::FundPending {
+        address: format!("{:?}", addr),
+        tx_hash: format!("{:?}", sent.tx_hash),
+    };
+    let _ = client_tx.send(pending_msg).await;
+
+    let chain = chain.clone();
+    let client_tx = client_tx.clone();
+    let outcome_tx = outcome_tx.clone();
+    tokio::spawn(async move {
+        let receipt = chain.get_receipt(sent).await;
+        let _ = outcome_tx
+            .send(FundingOutcome {
+                addr,
+                client_tx,
+                receipt,
+            })
+            .await;
+    });
 
     Ok(())
 }
 
-async fn handle_tick_event<T, P>(
-    provider: &P,
-    contract: &StockMarket::StockMarketInstance<T, &P>,
-    state: Arc<RwLock<AppState>>,
-) -> Result<()>
-where
-    T: Transport + Clone,
-    P: Provider<T> + WalletProvider,
-{
-    let nonce = {
-        let mut state_guard = state.write().await;
-        let nonce = state_guard.backend_nonce;
-        state_guard.backend_nonce += 1;
-        nonce
+async fn handle_funding_outcome(outcome: FundingOutcome, ctx: &BackendContext) {
+    let FundingOutcome {
+        addr,
+        client_tx,
+        receipt,
+    } = outcome;
+
+    let error_msg = match receipt {
+        Ok(receipt) if receipt.success => {
+            tracing::info!(
+                "✅ Funding tx confirmed: {:?} (block: {})",
+                receipt.tx_hash,
+                receipt.block_number
+            );
+            ctx.faucet.record(addr, ctx.config.faucet.amount).await;
+            let funded_msg = ServerMessage::Funded {
+                address: format!("{:?}", addr),
+                amount: ctx.config.faucet.amount.to_string(),
+            };
+            let _ = client_tx.send(funded_msg).await;
+            return;
+        }
+        Ok(receipt) => format!("Funding transaction failed: {:?}", receipt.tx_hash),
+        Err(e) => format!("Failed to confirm funding: {}", e),
     };
 
-    let max_fee_per_gas = U256::from(0x21d664903cu64);
-    let max_priority_fee = U256::from(1_000_000_000u64);
-    let gas_limit = 60_000u64;
-
-    let call = contract.tick();
-    let tx_req = call
-        .into_transaction_request()
-        .with_nonce(nonce)
-        .with_gas_limit(gas_limit)
-        .with_max_fee_per_gas(max_fee_per_gas.to::<u128>())
-        .with_max_priority_fee_per_gas(max_priority_fee.to::<u128>());
+    METRICS.funds_failed.inc();
+    tracing::error!("{}", error_msg);
+    ctx.faucet.release(addr).await;
+    let msg = ServerMessage::FundError {
+        address: format!("{:?}", addr),
+        error: error_msg,
+        reason: FundErrorReason::TxFailed,
+    };
+    let _ = client_tx.send(msg).await;
+}
 
-    let pending = provider.send_transaction(tx_req).await?;
-    let tx_hash = *pending.tx_hash();
-    tracing::info!("📤 Tick tx sent: {:?} (nonce: {})", tx_hash, nonce);
+async fn handle_tick_event<C: MarketChain>(chain: &C) -> Result<()> {
+    let sent = chain.tick().await?;
+    METRICS.ticks_sent.inc();
+    tracing::info!(
+        "📤 Tick tx sent: {:?} (nonce: {})",
+        sent.tx_hash,
+        sent.nonce
+    );
 
     Ok(())
 }
 
-pub async fn handle_restart_game<T, P>(
-    provider: P,
-    contract_addr: Address,
+/// Sends every transfer back to back on consecutive nonces, then waits for
+/// all receipts together, so refunding N players takes about one block
+/// instead of N. Stops submitting at the first send error; transfers already
+/// sent are still awaited.
+async fn fund_batch<C: MarketChain>(
+    chain: &C,
+    transfers: &[(Address, U256)],
+) -> Result<Vec<(Address, Result<Receipt>)>> {
+    let mut sent = Vec::with_capacity(transfers.len());
+    let mut send_error = None;
+    for &(addr, amount) in transfers {
+        match chain.send_transaction(addr, amount).await {
+            Ok(tx) => {
+                METRICS.funds_sent.inc();
+                tracing::info!("📤 Funding tx sent: {:?} (nonce: {})", tx.tx_hash, tx.nonce);
+                sent.push((addr, tx));
+            }
+            Err(e) => {
+                METRICS.funds_failed.inc();
+                send_error = Some(e);
+                break;
+            }
+        }
+    }
+
+    tracing::info!("Waiting for {} funding txs", sent.len());
+    let receipts = join_all(sent.iter().map(|(_, tx)| chain.get_receipt(*tx))).await;
+    let results: Vec<_> = sent
+        .into_iter()
+        .map(|(addr, _)| addr)
+        .zip(receipts)
+        .collect();
+    for (_, receipt) in &results {
+        if !receipt.as_ref().is_ok_and(|r| r.success) {
+            METRICS.funds_failed.inc();
+        }
+    }
+    tracing::info!(
+        "✅ Funding batch confirmed: {}/{} succeeded",
+        results
+            .iter()
+            .filter(|(_, receipt)| receipt.as_ref().is_ok_and(|r| r.success))
+            .count(),
+        transfers.len()
+    );
+
+    match send_error {
+        Some(e) => Err(e),
+        None => Ok(results),
+    }
+}
+
+pub async fn handle_restart_game<C: MarketChain>(
+    chain: C,
     state: Arc<RwLock<AppState>>,
     broadcast_tx: broadcast::Sender<ServerMessage>,
-) -> Result<()>
-where
-    T: Transport + Clone,
-    P: Provider<T> + WalletProvider,
-{
+    ctx: BackendContext,
+) -> Result<()> {
     tracing::info!("🔄 Starting game restart sequence");
 
-    let contract = StockMarket::new(contract_addr, &provider);
+    let BackendContext { config, .. } = ctx;
 
-    // Step 1: Fund all players back to 0.5 MON
-    tracing::info!("Step 1: Funding all players to 0.5 MON");
-    let funding_amount = U256::from(500_000_000_000_000_000u64); // 0.5 MON
-    let min_balance = U256::from(450_000_000_000_000_000u64); // 0.45 MON threshold
+    // Step 1: Top all players back up to the faucet amount
+    let funding_amount = config.faucet.amount;
+    let min_balance = config.faucet.refund_threshold;
+    tracing::info!("Step 1: Funding all players to {} wei", funding_amount);
 
     let addresses: Vec<Address> = {
         let state_guard = state.read().await;
@@ -193,182 +262,139 @@ where
 
     tracing::info!("Found {} players to fund", addresses.len());
 
-    for addr in addresses {
-        let balance = provider.get_balance(addr).await?;
-
+    let balances = join_all(addresses.iter().map(|addr| chain.get_balance(*addr))).await;
+    let mut transfers = Vec::new();
+    for (addr, balance) in addresses.into_iter().zip(balances) {
+        let balance = balance?;
         if balance < min_balance {
             tracing::info!("Funding {:?} (current: {} wei)", addr, balance);
-
-            let nonce = {
-                let mut state_guard = state.write().await;
-                let nonce = state_guard.backend_nonce;
-                state_guard.backend_nonce += 1;
-                nonce
-            };
-
-            let gas_price = U256::from(0x21d664903cu64);
-            let gas_limit = 25_000u64;
-
-            let tx = alloy::rpc::types::TransactionRequest::default()
-                .to(addr)
-                .value(funding_amount - balance)
-                .with_nonce(nonce)
-                .with_gas_limit(gas_limit)
-                .with_gas_price(gas_price.to::<u128>());
-
-            let pending = provider.send_transaction(tx).await?;
-            let tx_hash = *pending.tx_hash();
-            tracing::info!("📤 Funding tx sent: {:?} (nonce: {})", tx_hash, nonce);
-
-            // Wait for confirmation
-            loop {
-                match provider.get_transaction_receipt(tx_hash).await? {
-                    Some(receipt) => {
-                        tracing::info!(
-                            "✅ Funding confirmed: {:?} (status: {})",
-                            tx_hash,
-                            receipt.status()
-                        );
-                        if receipt.status() {
-                            let funded_msg = ServerMessage::Funded {
-                                address: format!("{:?}", addr),
-                                amount: funding_amount.to::<u64>(),
-                            };
-                            let _ = broadcast_tx.send(funded_msg);
-                        }
-                        break;
-                    }
-                    None => {
-                        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-                    }
-                }
-            }
+            transfers.push((addr, funding_amount - balance));
         } else {
             tracing::info!("Skipping {:?} (sufficient balance: {} wei)", addr, balance);
         }
     }
 
+    for (addr, receipt) in fund_batch(&chain, &transfers).await? {
+        match receipt {
+            Ok(receipt) if receipt.success => {
+                let funded_msg = ServerMessage::Funded {
+                    address: format!("{:?}", addr),
+                    amount: funding_amount.to_string(),
+                };
+                let _ = broadcast_tx.send(funded_msg);
+            }
+            Ok(receipt) => {
+                tracing::error!("❌ Funding {:?} failed: {:?}", addr, receipt.tx_hash)
+            }
+            Err(e) => tracing::error!("❌ Funding {:?} failed: {}", addr, e),
+        }
+    }
+
     // Step 2: Call reset() on contract
     tracing::info!("Step 2: Calling reset() on contract");
-    let nonce = {
-        let mut state_guard = state.write().await;
-        let nonce = state_guard.backend_nonce;
-        state_guard.backend_nonce += 1;
-        nonce
-    };
 
-    let max_fee_per_gas = U256::from(0x21d664903cu64);
-    let max_priority_fee = U256::from(1_000_000_000u64);
-    let gas_limit = 500_000u64;
-
-    let call = contract.reset();
-    let tx_req = call
-        .into_transaction_request()
-        .with_nonce(nonce)
-        .with_gas_limit(gas_limit)
-        .with_max_fee_per_gas(max_fee_per_gas.to::<u128>())
-        .with_max_priority_fee_per_gas(max_priority_fee.to::<u128>());
-
-    let pending = provider.send_transaction(tx_req).await?;
-    let reset_tx_hash = *pending.tx_hash();
-    tracing::info!("📤 Reset tx sent: {:?} (nonce: {})", reset_tx_hash, nonce);
+    let sent = chain.reset().await?;
+    state.write().await.reset_txs.insert(sent.tx_hash);
+    tracing::info!(
+        "📤 Reset tx sent: {:?} (nonce: {})",
+        sent.tx_hash,
+        sent.nonce
+    );
 
     // Step 3: Wait for reset to complete
     tracing::info!("Step 3: Waiting for reset confirmation");
-    loop {
-        match provider.get_transaction_receipt(reset_tx_hash).await? {
-            Some(receipt) => {
-                if receipt.status() {
-                    tracing::info!("✅ Reset confirmed: {:?}", reset_tx_hash);
-                } else {
-                    tracing::error!("❌ Reset failed: {:?}", reset_tx_hash);
-                    return Err(anyhow::anyhow!("Reset transaction failed"));
-                }
-                break;
-            }
-            None => {
-                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-            }
-        }
+    let receipt = chain.get_receipt(sent).await?;
+    if receipt.success {
+        tracing::info!("✅ Reset confirmed: {:?}", receipt.tx_hash);
+    } else {
+        tracing::error!("❌ Reset failed: {:?}", receipt.tx_hash);
+        return Err(anyhow::anyhow!("Reset transaction failed"));
     }
 
-    let game_duration = 200; // TODO
+    let game_duration = config.game.duration;
     // Step 4: Call start() on contract
     tracing::info!("Step 4: Starting new game ({game_duration} blocks)");
-    let nonce = {
-        let mut state_guard = state.write().await;
-        let nonce = state_guard.backend_nonce;
-        state_guard.backend_nonce += 1;
-        nonce
-    };
 
-    let call = contract.start(U256::from(game_duration));
-    let tx_req = call
-        .into_transaction_request()
-        .with_nonce(nonce)
-        .with_gas_limit(gas_limit)
-        .with_max_fee_per_gas(max_fee_per_gas.to::<u128>())
-        .with_max_priority_fee_per_gas(max_priority_fee.to::<u128>());
-
-    let pending = provider.send_transaction(tx_req).await?;
-    let start_tx_hash = *pending.tx_hash();
-    tracing::info!("📤 Start tx sent: {:?} (nonce: {})", start_tx_hash, nonce);
+    let sent = chain.start(game_duration).await?;
+    tracing::info!(
+        "📤 Start tx sent: {:?} (nonce: {})",
+        sent.tx_hash,
+        sent.nonce
+    );
 
-    loop {
-        match provider.get_transaction_receipt(start_tx_hash).await? {
-            Some(receipt) => {
-                if receipt.status() {
-                    tracing::info!("✅ Game started: {:?}", start_tx_hash);
-                } else {
-                    tracing::error!("❌ Start failed: {:?}", start_tx_hash);
-                    return Err(anyhow::anyhow!("Start transaction failed"));
-                }
-                break;
-            }
-            None => {
-                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-            }
-        }
+    let receipt = chain.get_receipt(sent).await?;
+    if receipt.success {
+        tracing::info!("✅ Game started: {:?}", receipt.tx_hash);
+    } else {
+        tracing::error!("❌ Start failed: {:?}", receipt.tx_hash);
+        return Err(anyhow::anyhow!("Start transaction failed"));
     }
 
     tracing::info!("🎮 Game restart sequence completed successfully");
     Ok(())
 }
 
-pub async fn backend_tx_executor<T, P>(
+/// Submits backend txs one at a time, so nonces are handed out in event
+/// order. Only ticks and fund submissions run here; funding confirmations are
+/// awaited in their own tasks and come back through `outcome_rx`.
+///
+/// On shutdown the queue is closed, what's already in it is still processed,
+/// and the executor returns once every funding tx in flight has an outcome.
+pub async fn backend_tx_executor<C: MarketChain>(
     mut rx: mpsc::Receiver<BackendTxEvent>,
-    provider: P,
-    contract_addr: Address,
+    chain: C,
     broadcast_tx: broadcast::Sender<ServerMessage>,
-    state: Arc<RwLock<AppState>>,
-) -> Result<()>
-where
-    T: Transport + Clone,
-    P: Provider<T> + WalletProvider,
-{
-    let contract = StockMarket::new(contract_addr, &provider);
-
-    let backend_addr = provider.default_signer_address();
-    tracing::info!("Backend wallet address: {:?}", backend_addr);
-    while let Some(event) = rx.recv().await {
+    ctx: BackendContext,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let (outcome_tx, mut outcome_rx) = mpsc::channel::<FundingOutcome>(100);
+
+    let backend_addr = chain.backend_address();
+    tracing::info!(
+        "Backend wallet address: {:?} (next nonce: {})",
+        backend_addr,
+        chain.nonce().await
+    );
+    let mut draining = false;
+    loop {
+        let event = tokio::select! {
+            event = rx.recv() => match event {
+                Some(event) => event,
+                None => break,
+            },
+            Some(outcome) = outcome_rx.recv() => {
+                handle_funding_outcome(outcome, &ctx).await;
+                continue;
+            }
+            _ = shutdown.cancelled(), if !draining => {
+                tracing::info!("🛑 Draining {} queued backend events", rx.len());
+                rx.close();
+                draining = true;
+                continue;
+            }
+        };
+
         match event {
-            BackendTxEvent::Fund(addr, client_tx) => {
+            BackendTxEvent::Fund(addr, ip, client_tx) => {
                 tracing::info!("Processing Fund event for {:?}", addr);
                 if let Err(e) = handle_fund_event(
-                    &provider,
-                    &contract,
+                    &chain,
                     addr,
+                    ip,
                     &broadcast_tx,
                     &client_tx,
-                    state.clone(),
+                    &outcome_tx,
+                    &ctx,
                 )
                 .await
                 {
+                    METRICS.funds_failed.inc();
                     let error_msg = format!("Failed to fund account: {}", e);
                     tracing::error!("{}", error_msg);
                     let msg = ServerMessage::FundError {
                         address: format!("{:?}", addr),
                         error: error_msg,
+                        reason: FundErrorReason::TxFailed,
                     };
                     let _ = client_tx.send(msg).await;
                 }
@@ -378,52 +404,22 @@ where
             }
             BackendTxEvent::Tick => {
                 tracing::info!("Processing Tick event");
-                if let Err(e) = handle_tick_event(&provider, &contract, state.clone()).await {
+                if let Err(e) = handle_tick_event(&chain).await {
+                    METRICS.ticks_failed.inc();
                     let error_msg = format!("Failed to process tick: {}", e);
                     tracing::error!("{}", error_msg);
 
                     if error_msg.contains("Already ticked this block") {
                         tracing::debug!("Block was already ticked (race condition, expected)");
-                    } else if error_msg.contains("higher priority") {
-                        tracing::warn!("⚠️  Higher priority transaction exists, jumping nonce +20");
-                        let mut state_guard = state.write().await;
-                        let old_nonce = state_guard.backend_nonce;
-                        state_guard.backend_nonce += 20;
-                        tracing::info!(
-                            "✅ Nonce jumped: {} -> {} (skipping stuck transactions)",
-                            old_nonce,
-                            state_guard.backend_nonce
-                        );
                     } else {
+                        if error_msg.contains("higher priority") {
+                            tracing::warn!("⚠️  Higher priority transaction exists for our nonce");
+                        }
                         tracing::warn!(
                             "⚠️  Tick transaction failed, resyncing nonce from chain..."
                         );
-                        let backend_address = provider.default_signer_address();
-                        match provider.get_transaction_count(backend_address).await {
-                            Ok(chain_nonce) => {
-                                let mut state_guard = state.write().await;
-                                let old_nonce = state_guard.backend_nonce;
-
-                                if chain_nonce > old_nonce {
-                                    state_guard.backend_nonce = chain_nonce;
-                                    tracing::info!(
-                                        "✅ Nonce resynced upward: {} -> {} (next block will retry)",
-                                        old_nonce,
-                                        chain_nonce
-                                    );
-                                } else if chain_nonce == old_nonce {
-                                    tracing::info!("✅ Nonce already in sync: {}", chain_nonce);
-                                } else {
-                                    tracing::warn!(
-                                        "⚠️  Chain nonce ({}) < local nonce ({}), keeping local (transactions pending)",
-                                        chain_nonce,
-                                        old_nonce
-                                    );
-                                }
-                            }
-                            Err(nonce_err) => {
-                                tracing::error!("Failed to fetch nonce from chain: {}", nonce_err);
-                            }
+                        if let Err(nonce_err) = chain.resync_nonce().await {
+                            tracing::error!("Failed to resync nonce from chain: {}", nonce_err);
                         }
                     }
                 }
@@ -431,5 +427,338 @@ where
         }
     }
 
+    drop(outcome_tx);
+    while let Some(outcome) = outcome_rx.recv().await {
+        handle_funding_outcome(outcome, &ctx).await;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chain::mock::MockChain, store::Store};
+    use std::net::Ipv4Addr;
+
+    const BACKEND: Address = Address::repeat_byte(0xba);
+    const ALICE: Address = Address::repeat_byte(0xa1);
+    const BOB: Address = Address::repeat_byte(0xb0);
+    const IP: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    fn setup(config: Config) -> (MockChain, BackendContext, Arc<Store>) {
+        let chain = MockChain::new(
+            BACKEND,
+            U256::from(100u64) * U256::from(10u64).pow(U256::from(18)),
+        );
+        let store = Arc::new(Store::temporary().unwrap());
+        let ctx = BackendContext {
+            faucet: Arc::new(Faucet::new(config.faucet.clone(), store.clone())),
+            config: Arc::new(config),
+        };
+        (chain, ctx, store)
+    }
+
+    /// Runs a fund request to completion and returns the last message the
+    /// client got.
+    async fn fund(chain: &MockChain, ctx: &BackendContext, addr: Address) -> ServerMessage {
+        let (broadcast_tx, _) = broadcast::channel(16);
+        let (client_tx, mut client_rx) = mpsc::channel(16);
+        let (outcome_tx, mut outcome_rx) = mpsc::channel(1);
+        handle_fund_event(chain, addr, IP, &broadcast_tx, &client_tx, &outcome_tx, ctx)
+            .await
+            .unwrap();
+        drop(outcome_tx);
+        if let Some(outcome) = outcome_rx.recv().await {
+            handle_funding_outcome(outcome, ctx).await;
+        }
+        drop(client_tx);
+        let mut last = None;
+        while let Some(msg) = client_rx.recv().await {
+            last = Some(msg);
+        }
+        last.expect("no reply to the client")
+    }
+
+    /// Feeds `events` through the executor and waits for it to drain them.
+    async fn execute(
+        chain: &MockChain,
+        ctx: &BackendContext,
+        events: Vec<BackendTxEvent>,
+    ) -> broadcast::Receiver<ServerMessage> {
+        let (broadcast_tx, broadcast_rx) = broadcast::channel(16);
+        let (tx, rx) = mpsc::channel(16);
+        for event in events {
+            tx.send(event).await.unwrap();
+        }
+        drop(tx);
+        backend_tx_executor(
+            rx,
+            chain.clone(),
+            broadcast_tx,
+            ctx.clone(),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        broadcast_rx
+    }
+
+    #[tokio::test]
+    async fn funds_a_new_address_once() {
+        let (chain, ctx, store) = setup(Config::default());
+        let amount = ctx.config.faucet.amount;
+
+        let msg = fund(&chain, &ctx, ALICE).await;
+        assert!(matches!(msg, ServerMessage::Funded { amount: a, .. } if a == amount.to_string()));
+        assert_eq!(chain.get_balance(ALICE).await.unwrap(), amount);
+        assert!(store.is_funded(ALICE).unwrap());
+
+        // Spending it all doesn't earn a second round.
+        chain.set_balance(ALICE, U256::ZERO);
+        let msg = fund(&chain, &ctx, ALICE).await;
+        assert!(matches!(
+            msg,
+            ServerMessage::FundError {
+                reason: FundErrorReason::AlreadyFunded,
+                ..
+            }
+        ));
+        assert_eq!(chain.nonce().await, 1);
+    }
+
+    #[tokio::test]
+    async fn already_funded_address_gets_its_position() {
+        let (chain, ctx, _) = setup(Config::default());
+        chain.set_balance(ALICE, U256::from(1));
+        chain.register(ALICE);
+
+        let (broadcast_tx, mut broadcast_rx) = broadcast::channel(16);
+        let (client_tx, mut client_rx) = mpsc::channel(16);
+        let (outcome_tx, _) = mpsc::channel(1);
+        handle_fund_event(
+            &chain,
+            ALICE,
+            IP,
+            &broadcast_tx,
+            &client_tx,
+            &outcome_tx,
+            &ctx,
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            client_rx.recv().await,
+            Some(ServerMessage::Funded { amount, .. }) if amount == "1"
+        ));
+        assert!(matches!(
+            broadcast_rx.recv().await,
+            Ok(ServerMessage::Position {
+                balance: 500,
+                holdings: 10,
+                ..
+            })
+        ));
+        assert_eq!(chain.nonce().await, 0);
+    }
+
+    #[tokio::test]
+    async fn already_funded_balance_above_u64_is_reported() {
+        let (chain, ctx, _) = setup(Config::default());
+        let balance = U256::from(u64::MAX) * U256::from(3);
+        chain.set_balance(ALICE, balance);
+
+        let msg = fund(&chain, &ctx, ALICE).await;
+        assert!(
+            matches!(msg, ServerMessage::Funded { amount, .. } if amount == balance.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_send_releases_the_reservation() {
+        let (chain, ctx, store) = setup(Config::default());
+
+        chain.reject_sends(true);
+        let (broadcast_tx, _) = broadcast::channel(16);
+        let (client_tx, _client_rx) = mpsc::channel(16);
+        let (outcome_tx, _) = mpsc::channel(1);
+        let result = handle_fund_event(
+            &chain,
+            ALICE,
+            IP,
+            &broadcast_tx,
+            &client_tx,
+            &outcome_tx,
+            &ctx,
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(!store.is_funded(ALICE).unwrap());
+
+        chain.reject_sends(false);
+        let msg = fund(&chain, &ctx, ALICE).await;
+        assert!(matches!(msg, ServerMessage::Funded { .. }));
+    }
+
+    #[tokio::test]
+    async fn lost_receipt_releases_the_reservation() {
+        let (chain, ctx, store) = setup(Config::default());
+
+        chain.lose_receipts(true);
+        let msg = fund(&chain, &ctx, ALICE).await;
+        assert!(matches!(
+            msg,
+            ServerMessage::FundError {
+                reason: FundErrorReason::TxFailed,
+                ..
+            }
+        ));
+        assert!(!store.is_funded(ALICE).unwrap());
+
+        chain.lose_receipts(false);
+        chain.set_balance(ALICE, U256::ZERO);
+        let msg = fund(&chain, &ctx, ALICE).await;
+        assert!(matches!(msg, ServerMessage::Funded { .. }));
+        assert!(store.is_funded(ALICE).unwrap());
+    }
+
+    #[tokio::test]
+    async fn enforces_the_per_ip_limit() {
+        let mut config = Config::default();
+        config.faucet.per_ip_per_hour = 1;
+        let (chain, ctx, _) = setup(config);
+
+        assert!(matches!(
+            fund(&chain, &ctx, ALICE).await,
+            ServerMessage::Funded { .. }
+        ));
+        assert!(matches!(
+            fund(&chain, &ctx, BOB).await,
+            ServerMessage::FundError {
+                reason: FundErrorReason::IpLimit,
+                ..
+            }
+        ));
+        assert_eq!(chain.get_balance(BOB).await.unwrap(), U256::ZERO);
+    }
+
+    #[tokio::test]
+    async fn shutdown_drains_the_queue() {
+        let (chain, ctx, store) = setup(Config::default());
+        let (broadcast_tx, _) = broadcast::channel(16);
+        let (client_tx, mut client_rx) = mpsc::channel(16);
+        let (tx, rx) = mpsc::channel(16);
+        tx.send(BackendTxEvent::Fund(ALICE, IP, client_tx))
+            .await
+            .unwrap();
+        tx.send(BackendTxEvent::GameOver).await.unwrap();
+
+        // The sender stays alive, so only the shutdown can end the executor.
+        let shutdown = CancellationToken::new();
+        shutdown.cancel();
+        backend_tx_executor(rx, chain.clone(), broadcast_tx, ctx, shutdown)
+            .await
+            .unwrap();
+
+        assert!(store.is_funded(ALICE).unwrap());
+        let mut last = None;
+        while let Ok(msg) = client_rx.try_recv() {
+            last = Some(msg);
+        }
+        assert!(matches!(last, Some(ServerMessage::Funded { .. })));
+        assert!(tx.send(BackendTxEvent::Tick).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn ticks_once_per_block() {
+        let (chain, ctx, _) = setup(Config::default());
+        chain.start(10).await.unwrap();
+        chain.mine_block();
+
+        execute(&chain, &ctx, vec![BackendTxEvent::Tick]).await;
+        let block = chain.block();
+        let price = chain.price();
+        assert_eq!(chain.last_tick_block(), block);
+
+        // A second tick in the same block is sent but reverts.
+        execute(&chain, &ctx, vec![BackendTxEvent::Tick]).await;
+        assert_eq!(chain.nonce().await, 3);
+        assert_eq!(chain.price(), price);
+
+        chain.mine_block();
+        execute(&chain, &ctx, vec![BackendTxEvent::Tick]).await;
+        assert_eq!(chain.last_tick_block(), block + 1);
+    }
+
+    #[tokio::test]
+    async fn executor_survives_a_failed_tick() {
+        let (chain, ctx, _) = setup(Config::default());
+        chain.start(10).await.unwrap();
+        chain.mine_block();
+
+        chain.reject_sends(true);
+        let mut broadcast_rx = execute(
+            &chain,
+            &ctx,
+            vec![BackendTxEvent::Tick, BackendTxEvent::GameOver],
+        )
+        .await;
+        assert!(matches!(
+            broadcast_rx.recv().await,
+            Ok(ServerMessage::GameEnded)
+        ));
+        assert_eq!(chain.last_tick_block(), 0);
+
+        chain.reject_sends(false);
+        execute(&chain, &ctx, vec![BackendTxEvent::Tick]).await;
+        assert_eq!(chain.last_tick_block(), chain.block());
+    }
+
+    #[tokio::test]
+    async fn restart_tops_up_players_and_starts_a_new_game() {
+        let mut config = Config::default();
+        config.game.duration = 20;
+        let (chain, ctx, store) = setup(config);
+        let amount = ctx.config.faucet.amount;
+
+        let state = Arc::new(RwLock::new(AppState::new(store, &[10]).unwrap()));
+        {
+            let mut state_guard = state.write().await;
+            state_guard.names.insert(ALICE, "alice".to_string());
+            state_guard.names.insert(BOB, "bob".to_string());
+        }
+        chain.set_balance(ALICE, amount);
+        chain.set_balance(BOB, U256::from(1));
+        chain.register(ALICE);
+        chain.register(BOB);
+        chain.start(5).await.unwrap();
+        chain.mine_block();
+        chain.tick().await.unwrap();
+        chain.buy(ALICE, 3).unwrap();
+        chain.sell(BOB, 10).unwrap();
+        chain.mine_block();
+
+        let (broadcast_tx, mut broadcast_rx) = broadcast::channel(16);
+        handle_restart_game(chain.clone(), state, broadcast_tx, ctx)
+            .await
+            .unwrap();
+
+        assert_eq!(chain.get_balance(ALICE).await.unwrap(), amount);
+        assert_eq!(chain.get_balance(BOB).await.unwrap(), amount);
+        match broadcast_rx.recv().await {
+            Ok(ServerMessage::Funded { address, .. }) => {
+                assert_eq!(address, format!("{:?}", BOB))
+            }
+            other => panic!("expected Funded for bob, got {:?}", other),
+        }
+        assert!(broadcast_rx.try_recv().is_err());
+
+        for player in [ALICE, BOB] {
+            assert_eq!(chain.get_credits(player).await.unwrap(), 500);
+            assert_eq!(chain.get_holdings(player).await.unwrap(), 10);
+        }
+        assert_eq!(chain.price(), 50);
+        let block = chain.block();
+        assert_eq!(chain.game(), (block, block + 20));
+    }
+}
@@ -0,0 +1,55 @@
+use crate::{AppState, chain_events, ws::ServerMessage};
+use alloy::{primitives::Address, providers::Provider, rpc::types::Filter, transports::Transport};
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::sync::{RwLock, broadcast};
+
+// Public RPC endpoints cap the block range of a single eth_getLogs call.
+const PAGE_SIZE: u64 = 100;
+
+/// Replays contract logs in `[from_block, to_block]` through the same path as
+/// the live stream. Returns the number of logs fetched.
+pub async fn backfill_logs<T, P>(
+    provider: &P,
+    contract_addr: Address,
+    from_block: u64,
+    to_block: u64,
+    state: &Arc<RwLock<AppState>>,
+    broadcast_tx: &broadcast::Sender<ServerMessage>,
+) -> Result<usize>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    tracing::info!(
+        "⏪ Backfilling logs from block {} to {}",
+        from_block,
+        to_block
+    );
+
+    let mut fetched = 0;
+    let mut page_start = from_block;
+    while page_start <= to_block {
+        let page_end = (page_start + PAGE_SIZE - 1).min(to_block);
+        let filter = Filter::new()
+            .address(contract_addr)
+            .from_block(page_start)
+            .to_block(page_end);
+
+        let logs = provider.get_logs(&filter).await?;
+        tracing::debug!(
+            "Fetched {} logs for blocks {}..={}",
+            logs.len(),
+            page_start,
+            page_end
+        );
+        for log in &logs {
+            chain_events::handle_log(log, state, broadcast_tx).await?;
+        }
+        fetched += logs.len();
+        page_start = page_end + 1;
+    }
+
+    tracing::info!("⏩ Backfill complete: {} logs", fetched);
+    Ok(fetched)
+}
@@ -0,0 +1,144 @@
+use crate::ws::Candle;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Builds OHLC candles from price updates over fixed block windows. A window
+/// of `w` blocks covers `[n * w, (n + 1) * w)`; a candle closes when the
+/// first update of the next window arrives. Only the open candles live here,
+/// closed ones are handed to the caller to persist.
+pub struct CandleAggregator {
+    windows: BTreeSet<u64>,
+    open: BTreeMap<u64, Candle>,
+}
+
+impl CandleAggregator {
+    pub fn new(windows: &[u64]) -> Self {
+        Self {
+            windows: windows.iter().copied().collect(),
+            open: BTreeMap::new(),
+        }
+    }
+
+    pub fn windows(&self) -> impl Iterator<Item = u64> + '_ {
+        self.windows.iter().copied()
+    }
+
+    pub fn tracks(&self, window: u64) -> bool {
+        self.windows.contains(&window)
+    }
+
+    /// The candle still collecting updates for `window`, if any has arrived.
+    pub fn open_candle(&self, window: u64) -> Option<&Candle> {
+        self.open.get(&window)
+    }
+
+    /// Feeds a price at `block_number` and returns the candles it closed.
+    pub fn on_price(&mut self, block_number: u64, price: u64) -> Vec<Candle> {
+        let mut finished = Vec::new();
+        for window in self.windows().collect::<Vec<_>>() {
+            if let Some(candle) = self.roll(window, block_number, price) {
+                finished.push(candle);
+            }
+            let candle = self.open.get_mut(&window).expect("rolled above");
+            candle.high = candle.high.max(price);
+            candle.low = candle.low.min(price);
+            if block_number >= candle.start_block {
+                candle.close = price;
+            }
+        }
+        finished
+    }
+
+    /// Adds traded shares to the candles covering `block_number`. `price` opens
+    /// a candle if no update has arrived for that window yet.
+    pub fn on_volume(&mut self, block_number: u64, quantity: u64, price: u64) -> Vec<Candle> {
+        let mut finished = Vec::new();
+        for window in self.windows().collect::<Vec<_>>() {
+            if let Some(candle) = self.roll(window, block_number, price) {
+                finished.push(candle);
+            }
+            let candle = self.open.get_mut(&window).expect("rolled above");
+            candle.volume += quantity;
+        }
+        finished
+    }
+
+    /// Makes sure the open candle for `window` covers `block_number`, closing
+    /// the previous one if it belongs to an earlier window. Updates for blocks
+    /// before the open candle (late backfill) are folded into it.
+    fn roll(&mut self, window: u64, block_number: u64, price: u64) -> Option<Candle> {
+        let start_block = block_number - block_number % window;
+        let fresh = Candle {
+            window,
+            start_block,
+            end_block: start_block + window - 1,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: 0,
+        };
+
+        let current = self.open.entry(window).or_insert_with(|| fresh.clone());
+        if start_block <= current.start_block {
+            return None;
+        }
+        Some(std::mem::replace(current, fresh))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_update_of_the_next_window_closes_the_candle() {
+        let mut candles = CandleAggregator::new(&[10]);
+        assert!(candles.on_price(10, 50).is_empty());
+        assert!(candles.on_price(15, 70).is_empty());
+        assert!(candles.on_price(19, 40).is_empty());
+
+        let closed = candles.on_price(20, 45);
+        assert_eq!(
+            closed,
+            vec![Candle {
+                window: 10,
+                start_block: 10,
+                end_block: 19,
+                open: 50,
+                high: 70,
+                low: 40,
+                close: 40,
+                volume: 0,
+            }]
+        );
+        let open = candles.open_candle(10).unwrap();
+        assert_eq!((open.start_block, open.open, open.close), (20, 45, 45));
+    }
+
+    #[test]
+    fn late_updates_fold_into_the_open_candle() {
+        let mut candles = CandleAggregator::new(&[10]);
+        candles.on_price(10, 50);
+        candles.on_price(20, 60);
+
+        // A trade backfilled for block 15, whose candle is already closed.
+        assert!(candles.on_volume(15, 3, 80).is_empty());
+        assert!(candles.on_price(15, 80).is_empty());
+        let open = candles.open_candle(10).unwrap();
+        assert_eq!(open.start_block, 20);
+        assert_eq!((open.high, open.close, open.volume), (80, 60, 3));
+    }
+
+    #[test]
+    fn volume_adds_up_per_window() {
+        let mut candles = CandleAggregator::new(&[5, 10]);
+        candles.on_volume(10, 2, 50);
+        candles.on_volume(12, 1, 50);
+        let closed = candles.on_volume(16, 4, 55);
+
+        assert_eq!(closed.len(), 1);
+        assert_eq!((closed[0].window, closed[0].volume), (5, 3));
+        assert_eq!(candles.open_candle(5).unwrap().volume, 4);
+        assert_eq!(candles.open_candle(10).unwrap().volume, 7);
+    }
+}
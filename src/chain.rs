@@ -0,0 +1,208 @@
+use crate::{
+    backend::StockMarket,
+    config::Config,
+    fees::{FeeOracle, TxClass},
+    nonce::NonceManager,
+};
+use alloy::{
+    network::TransactionBuilder,
+    primitives::{Address, TxHash, U256},
+    providers::{Provider, WalletProvider},
+    rpc::types::TransactionRequest,
+    transports::Transport,
+};
+use anyhow::Result;
+use std::{future::Future, marker::PhantomData, sync::Arc};
+
+#[cfg(test)]
+pub mod mock;
+
+/// A backend tx the node accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SentTx {
+    pub nonce: u64,
+    pub tx_hash: TxHash,
+}
+
+/// The parts of a mined tx the backend looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Receipt {
+    pub tx_hash: TxHash,
+    pub block_number: u64,
+    pub success: bool,
+}
+
+/// Everything the backend does on chain, so faucet, tick and restart logic
+/// can run against a node or the in-memory `mock::MockChain`. Sends go out
+/// from the backend wallet on its next nonce and return once the node has
+/// accepted them.
+pub trait MarketChain: Clone + Send + Sync + 'static {
+    fn backend_address(&self) -> Address;
+
+    /// Native balance of `address`, in wei.
+    fn get_balance(&self, address: Address) -> impl Future<Output = Result<U256>> + Send;
+
+    /// In-game credits of `address` (the contract's `getBalance`).
+    fn get_credits(&self, address: Address) -> impl Future<Output = Result<u64>> + Send;
+
+    fn get_holdings(&self, address: Address) -> impl Future<Output = Result<u64>> + Send;
+
+    /// Transfers `value` wei to `to`.
+    fn send_transaction(
+        &self,
+        to: Address,
+        value: U256,
+    ) -> impl Future<Output = Result<SentTx>> + Send;
+
+    /// Waits until `tx` is mined.
+    fn get_receipt(&self, tx: SentTx) -> impl Future<Output = Result<Receipt>> + Send;
+
+    fn tick(&self) -> impl Future<Output = Result<SentTx>> + Send;
+
+    fn reset(&self) -> impl Future<Output = Result<SentTx>> + Send;
+
+    /// Starts a game of `length` blocks from the block it's mined in.
+    fn start(&self, length: u64) -> impl Future<Output = Result<SentTx>> + Send;
+
+    /// Nonce the next send will use.
+    fn nonce(&self) -> impl Future<Output = u64> + Send;
+
+    /// Re-reads the nonce from the chain after a send went wrong.
+    fn resync_nonce(&self) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// `MarketChain` on a real node: sends go through the `NonceManager` and are
+/// priced by the `FeeOracle`, with gas limits from `[gas]`.
+pub struct AlloyChain<T, P> {
+    provider: P,
+    contract_address: Address,
+    nonces: Arc<NonceManager>,
+    fees: Arc<FeeOracle>,
+    config: Arc<Config>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T, P: Clone> Clone for AlloyChain<T, P> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: self.provider.clone(),
+            contract_address: self.contract_address,
+            nonces: self.nonces.clone(),
+            fees: self.fees.clone(),
+            config: self.config.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, P> AlloyChain<T, P>
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    pub fn new(
+        provider: P,
+        nonces: Arc<NonceManager>,
+        fees: Arc<FeeOracle>,
+        config: Arc<Config>,
+    ) -> Self {
+        Self {
+            provider,
+            contract_address: config.contract_address,
+            nonces,
+            fees,
+            config,
+            _phantom: PhantomData,
+        }
+    }
+
+    async fn send(&self, tx: TransactionRequest, class: TxClass) -> Result<SentTx> {
+        let fees = self.fees.fees(&self.provider, class).await;
+        let (nonce, tx_hash) = self
+            .nonces
+            .send(&self.provider, fees.apply(tx), class)
+            .await?;
+        Ok(SentTx { nonce, tx_hash })
+    }
+
+    fn contract(&self) -> StockMarket::StockMarketInstance<T, &P> {
+        StockMarket::new(self.contract_address, &self.provider)
+    }
+}
+
+impl<T, P> MarketChain for AlloyChain<T, P>
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    fn backend_address(&self) -> Address {
+        self.nonces.address()
+    }
+
+    async fn get_balance(&self, address: Address) -> Result<U256> {
+        Ok(self.provider.get_balance(address).await?)
+    }
+
+    async fn get_credits(&self, address: Address) -> Result<u64> {
+        Ok(self.contract().getBalance(address).call().await?._0.to())
+    }
+
+    async fn get_holdings(&self, address: Address) -> Result<u64> {
+        Ok(self.contract().getHoldings(address).call().await?._0.to())
+    }
+
+    async fn send_transaction(&self, to: Address, value: U256) -> Result<SentTx> {
+        let tx = TransactionRequest::default()
+            .to(to)
+            .value(value)
+            .with_gas_limit(self.config.gas.fund_gas_limit);
+        self.send(tx, TxClass::Relaxed).await
+    }
+
+    async fn get_receipt(&self, tx: SentTx) -> Result<Receipt> {
+        let receipt = self
+            .nonces
+            .wait_for_receipt(&self.provider, tx.nonce, tx.tx_hash)
+            .await?;
+        Ok(Receipt {
+            tx_hash: receipt.transaction_hash,
+            block_number: receipt.block_number.unwrap_or_default(),
+            success: receipt.status(),
+        })
+    }
+
+    async fn tick(&self) -> Result<SentTx> {
+        let tx = self
+            .contract()
+            .tick()
+            .into_transaction_request()
+            .with_gas_limit(self.config.gas.tick_gas_limit);
+        self.send(tx, TxClass::Tick).await
+    }
+
+    async fn reset(&self) -> Result<SentTx> {
+        let tx = self
+            .contract()
+            .reset()
+            .into_transaction_request()
+            .with_gas_limit(self.config.gas.admin_gas_limit);
+        self.send(tx, TxClass::Urgent).await
+    }
+
+    async fn start(&self, length: u64) -> Result<SentTx> {
+        let tx = self
+            .contract()
+            .start(U256::from(length))
+            .into_transaction_request()
+            .with_gas_limit(self.config.gas.admin_gas_limit);
+        self.send(tx, TxClass::Urgent).await
+    }
+
+    async fn nonce(&self) -> u64 {
+        self.nonces.next_nonce().await
+    }
+
+    async fn resync_nonce(&self) -> Result<()> {
+        self.nonces.resync(&self.provider).await
+    }
+}
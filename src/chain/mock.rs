@@ -0,0 +1,281 @@
+//! In-memory `MarketChain` that follows StockMarket.sol's rules. Every tx is
+//! mined as soon as it's sent, in the current block; blocks only advance on
+//! `mine_block`, so tests control exactly what lands where.
+
+use super::{MarketChain, Receipt, SentTx};
+use alloy::primitives::{Address, TxHash, U256, keccak256};
+use anyhow::{Result, anyhow, bail};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+const INITIAL_PRICE: u64 = 50;
+const INITIAL_CREDITS: u64 = 500;
+const INITIAL_STOCKS: u64 = 10;
+const MIN_PRICE: i64 = 1;
+const MAX_PRICE: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct User {
+    pub credits: u64,
+    pub holdings: u64,
+}
+
+struct Market {
+    backend: Address,
+    block: u64,
+    nonce: u64,
+    balances: HashMap<Address, U256>,
+    users: BTreeMap<Address, User>,
+    price: u64,
+    last_tick_block: u64,
+    start_block: u64,
+    end_block: u64,
+    receipts: HashMap<TxHash, Receipt>,
+    reject_sends: bool,
+    lose_receipts: bool,
+}
+
+impl Market {
+    /// Mines a backend tx: `apply` runs the contract logic and its error
+    /// becomes a reverted receipt, like a tx sent with a fixed gas limit.
+    fn mine(&mut self, apply: impl FnOnce(&mut Self) -> Result<()>) -> Result<SentTx> {
+        if self.reject_sends {
+            bail!("mock node rejected the transaction");
+        }
+        let nonce = self.nonce;
+        self.nonce += 1;
+        let tx_hash = keccak256([self.backend.as_slice(), &nonce.to_be_bytes()].concat());
+        let success = apply(self).is_ok();
+        self.receipts.insert(
+            tx_hash,
+            Receipt {
+                tx_hash,
+                block_number: self.block,
+                success,
+            },
+        );
+        Ok(SentTx { nonce, tx_hash })
+    }
+
+    fn only_active(&self) -> Result<()> {
+        if self.start_block == 0 {
+            bail!("Contract not started");
+        }
+        if self.block > self.end_block {
+            bail!("Contract has ended");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct MockChain {
+    market: Arc<Mutex<Market>>,
+}
+
+impl MockChain {
+    /// A chain at block 1 where `backend` owns the contract and holds
+    /// `balance` wei.
+    pub fn new(backend: Address, balance: U256) -> Self {
+        Self {
+            market: Arc::new(Mutex::new(Market {
+                backend,
+                block: 1,
+                nonce: 0,
+                balances: HashMap::from([(backend, balance)]),
+                users: BTreeMap::new(),
+                price: INITIAL_PRICE,
+                last_tick_block: 0,
+                start_block: 0,
+                end_block: 0,
+                receipts: HashMap::new(),
+                reject_sends: false,
+                lose_receipts: false,
+            })),
+        }
+    }
+
+    fn market(&self) -> std::sync::MutexGuard<'_, Market> {
+        self.market.lock().unwrap()
+    }
+
+    pub fn mine_block(&self) {
+        self.market().block += 1;
+    }
+
+    pub fn block(&self) -> u64 {
+        self.market().block
+    }
+
+    pub fn price(&self) -> u64 {
+        self.market().price
+    }
+
+    pub fn last_tick_block(&self) -> u64 {
+        self.market().last_tick_block
+    }
+
+    pub fn game(&self) -> (u64, u64) {
+        let market = self.market();
+        (market.start_block, market.end_block)
+    }
+
+    pub fn user(&self, address: Address) -> Option<User> {
+        self.market().users.get(&address).copied()
+    }
+
+    /// Makes every following send fail before reaching the "mempool".
+    pub fn reject_sends(&self, reject: bool) {
+        self.market().reject_sends = reject;
+    }
+
+    /// Makes receipt waits fail, like a tx dropped from the mempool.
+    pub fn lose_receipts(&self, lose: bool) {
+        self.market().lose_receipts = lose;
+    }
+
+    pub fn set_balance(&self, address: Address, balance: U256) {
+        self.market().balances.insert(address, balance);
+    }
+
+    /// `register()` from `address`. Player txs don't cost gas here.
+    pub fn register(&self, address: Address) {
+        self.market().users.entry(address).or_insert(User {
+            credits: INITIAL_CREDITS,
+            holdings: INITIAL_STOCKS,
+        });
+    }
+
+    pub fn buy(&self, address: Address, amount: u64) -> Result<()> {
+        let mut market = self.market();
+        market.only_active()?;
+        let cost = amount * market.price;
+        let user = market
+            .users
+            .get_mut(&address)
+            .ok_or_else(|| anyhow!("Not registered"))?;
+        if user.credits < cost {
+            bail!("Insufficient credits");
+        }
+        user.credits -= cost;
+        user.holdings += amount;
+        Ok(())
+    }
+
+    pub fn sell(&self, address: Address, amount: u64) -> Result<()> {
+        let mut market = self.market();
+        market.only_active()?;
+        let revenue = amount * market.price;
+        let user = market
+            .users
+            .get_mut(&address)
+            .ok_or_else(|| anyhow!("Not registered"))?;
+        if user.holdings < amount {
+            bail!("Insufficient holdings");
+        }
+        user.holdings -= amount;
+        user.credits += revenue;
+        Ok(())
+    }
+}
+
+impl MarketChain for MockChain {
+    fn backend_address(&self) -> Address {
+        self.market().backend
+    }
+
+    async fn get_balance(&self, address: Address) -> Result<U256> {
+        Ok(self
+            .market()
+            .balances
+            .get(&address)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    async fn get_credits(&self, address: Address) -> Result<u64> {
+        Ok(self.user(address).map_or(0, |user| user.credits))
+    }
+
+    async fn get_holdings(&self, address: Address) -> Result<u64> {
+        Ok(self.user(address).map_or(0, |user| user.holdings))
+    }
+
+    async fn send_transaction(&self, to: Address, value: U256) -> Result<SentTx> {
+        self.market().mine(|market| {
+            let backend = market.backend;
+            let from = market.balances.entry(backend).or_default();
+            if *from < value {
+                bail!("insufficient funds");
+            }
+            *from -= value;
+            *market.balances.entry(to).or_default() += value;
+            Ok(())
+        })
+    }
+
+    async fn get_receipt(&self, tx: SentTx) -> Result<Receipt> {
+        let market = self.market();
+        if market.lose_receipts {
+            bail!("no receipt for nonce {}", tx.nonce);
+        }
+        market
+            .receipts
+            .get(&tx.tx_hash)
+            .copied()
+            .ok_or_else(|| anyhow!("unknown tx {:?}", tx.tx_hash))
+    }
+
+    async fn tick(&self) -> Result<SentTx> {
+        self.market().mine(|market| {
+            if market.last_tick_block >= market.block {
+                bail!("Already ticked this block");
+            }
+            market.only_active()?;
+            market.last_tick_block = market.block;
+
+            // Deterministic stand-in for the contract's block-based seed.
+            let seed = keccak256([market.block.to_be_bytes(), market.price.to_be_bytes()].concat());
+            let change = i64::from(seed[31] % 21) - 10;
+            let mut price = market.price as i64 + change;
+            if price < MIN_PRICE {
+                price = MIN_PRICE * 2 - price;
+            }
+            if price > MAX_PRICE {
+                price = MAX_PRICE * 2 - price;
+            }
+            market.price = price as u64;
+            Ok(())
+        })
+    }
+
+    async fn reset(&self) -> Result<SentTx> {
+        self.market().mine(|market| {
+            market.price = INITIAL_PRICE;
+            market.last_tick_block = 0;
+            for user in market.users.values_mut() {
+                user.credits = INITIAL_CREDITS;
+                user.holdings = INITIAL_STOCKS;
+            }
+            Ok(())
+        })
+    }
+
+    async fn start(&self, length: u64) -> Result<SentTx> {
+        self.market().mine(|market| {
+            market.start_block = market.block;
+            market.end_block = market.block + length;
+            Ok(())
+        })
+    }
+
+    async fn nonce(&self) -> u64 {
+        self.market().nonce
+    }
+
+    async fn resync_nonce(&self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -1,108 +1,289 @@
-use crate::{AppState, backend::StockMarket, ws::ServerMessage};
+use crate::{
+    AppState,
+    backend::StockMarket,
+    trades,
+    ws::{Candle, PricePoint, ServerMessage},
+};
 use alloy::{rpc::types::Log, sol_types::SolEvent};
-use futures_util::{Stream, StreamExt};
 use std::sync::Arc;
 use tokio::sync::{RwLock, broadcast};
 
-pub async fn process_chain_events(
-    mut stream: impl Stream<Item = Log> + Unpin,
-    state: Arc<RwLock<AppState>>,
-    broadcast_tx: broadcast::Sender<ServerMessage>,
+/// Applies a single contract log to `state` and broadcasts the matching
+/// message. Logs already in `seen_logs` are ignored, so the same log can be
+/// fed from both backfill and the live subscription.
+pub async fn handle_log(
+    log: &Log,
+    state: &Arc<RwLock<AppState>>,
+    broadcast_tx: &broadcast::Sender<ServerMessage>,
 ) -> anyhow::Result<()> {
-    while let Some(log) = stream.next().await {
-        let key = (log.transaction_hash.unwrap(), log.log_index.unwrap());
+    let key = (log.transaction_hash.unwrap(), log.log_index.unwrap());
+
+    {
+        let mut state_guard = state.write().await;
+        if state_guard.seen_logs.contains(&key) {
+            return Ok(());
+        }
+        state_guard.seen_logs.insert(key);
+        if let Err(e) = state_guard.store.insert_seen_log(key) {
+            tracing::error!("Failed to persist seen log {:?}: {}", key, e);
+        }
+    }
+
+    match log.topic0() {
+        Some(&StockMarket::PriceUpdate::SIGNATURE_HASH) => {
+            let event = StockMarket::PriceUpdate::decode_log(&log.inner, true)?;
+            let new_price: u64 = event.newPrice.to();
+            let block_number: u64 = event.blockNumber.to();
+
+            tracing::info!("📈 Price update: {} (block {})", new_price, block_number);
 
-        {
             let mut state_guard = state.write().await;
-            if state_guard.seen_logs.contains(&key) {
-                continue;
+            state_guard.current_price = new_price;
+            state_guard.push_price(PricePoint {
+                block_number,
+                price: new_price,
+            });
+            if let Err(e) = state_guard.store.record_price(block_number, new_price) {
+                tracing::error!("Failed to persist price for block {}: {}", block_number, e);
             }
-            state_guard.seen_logs.insert(key);
-        }
 
-        match log.topic0() {
-            Some(&StockMarket::PriceUpdate::SIGNATURE_HASH) => {
-                let event = StockMarket::PriceUpdate::decode_log(&log.inner, true)?;
-                let new_price: u64 = event.newPrice.to();
-                let block_number: u64 = event.blockNumber.to();
+            let closed = state_guard.candles.on_price(block_number, new_price);
+            publish_candles(&state_guard, closed, broadcast_tx);
 
-                tracing::info!("📈 Price update: {} (block {})", new_price, block_number);
+            let msg = ServerMessage::PriceUpdate {
+                new_price,
+                block_number,
+            };
+            let _ = broadcast_tx.send(msg);
+        }
+        Some(&StockMarket::Position::SIGNATURE_HASH) => {
+            let event = StockMarket::Position::decode_log(&log.inner, true)?;
+            let user_addr = event.user;
+            let balance: u64 = event.balance.to();
+            let holdings: u64 = event.holdings.to();
+            let block_number: u64 = event.blockNumber.to();
 
-                let mut state_guard = state.write().await;
-                state_guard.current_price = new_price;
+            tracing::info!(
+                "💼 Position update: {:?} | balance: {}, holdings: {} (block {})",
+                user_addr,
+                balance,
+                holdings,
+                block_number
+            );
 
-                let msg = ServerMessage::PriceUpdate {
-                    new_price,
+            let mut state_guard = state.write().await;
+            let in_game = matches!(
+                (state_guard.game_start_block, state_guard.game_end_block),
+                (Some(start), Some(end)) if (start..=end).contains(&block_number)
+            ) && !state_guard.reset_txs.contains(&key.0);
+            let previous = state_guard
+                .balances
+                .get(&user_addr)
+                .copied()
+                .zip(state_guard.holdings.get(&user_addr).copied());
+            state_guard.balances.insert(user_addr, balance);
+            state_guard.holdings.insert(user_addr, holdings);
+            if in_game
+                && let Some(trade) = trades::diff(
+                    user_addr,
+                    previous,
+                    (balance, holdings),
                     block_number,
-                };
-                let _ = broadcast_tx.send(msg);
-            }
-            Some(&StockMarket::Position::SIGNATURE_HASH) => {
-                let event = StockMarket::Position::decode_log(&log.inner, true)?;
-                let user_addr = event.user;
-                let balance: u64 = event.balance.to();
-                let holdings: u64 = event.holdings.to();
-                let block_number: u64 = event.blockNumber.to();
-
+                    key.0,
+                )
+            {
                 tracing::info!(
-                    "💼 Position update: {:?} | balance: {}, holdings: {} (block {})",
+                    "🔁 Trade: {:?} {:?} {} @ {}",
                     user_addr,
-                    balance,
-                    holdings,
-                    block_number
+                    trade.side,
+                    trade.quantity,
+                    trade.price
                 );
-
-                let mut state_guard = state.write().await;
-                state_guard.balances.insert(user_addr, balance);
-                state_guard.holdings.insert(user_addr, holdings);
-                state_guard.last_position_block = block_number;
-
-                let msg = ServerMessage::Position {
-                    address: format!("{:?}", user_addr),
-                    balance,
-                    holdings,
-                    block_number,
-                };
-                let _ = broadcast_tx.send(msg);
-            }
-            Some(&StockMarket::NewUser::SIGNATURE_HASH) => {
-                let event = StockMarket::NewUser::decode_log(&log.inner, true)?;
-                let user_addr = event.user;
-
-                tracing::info!("👤 New user registered: {:?}", user_addr);
-
-                let msg = ServerMessage::Position {
-                    address: format!("{:?}", user_addr),
-                    balance: 1000,
-                    holdings: 0,
-                    block_number: 0,
-                };
-                let _ = broadcast_tx.send(msg);
+                if let Err(e) =
+                    state_guard
+                        .store
+                        .insert_trade(user_addr, block_number, key.1, &trade)
+                {
+                    tracing::error!("Failed to record trade for {:?}: {}", user_addr, e);
+                }
+                let closed =
+                    state_guard
+                        .candles
+                        .on_volume(block_number, trade.quantity, trade.price);
+                publish_candles(&state_guard, closed, broadcast_tx);
             }
-            Some(&StockMarket::Started::SIGNATURE_HASH) => {
-                let event = StockMarket::Started::decode_log(&log.inner, true)?;
-                let start_block: u64 = event.startBlock.to();
-                let end_block: u64 = event.endBlock.to();
-
-                tracing::info!("🎮 Game started: blocks {} to {}", start_block, end_block);
-
-                let mut state_guard = state.write().await;
-                state_guard.game_start_block = Some(start_block);
-                state_guard.game_end_block = Some(end_block);
-
-                let msg = ServerMessage::GameStarted {
-                    start_height: start_block,
-                    end_height: end_block,
-                };
-                let _ = broadcast_tx.send(msg);
+            state_guard.last_position_block = block_number;
+            if let Err(e) = state_guard.store.set_position(user_addr, balance, holdings) {
+                tracing::error!("Failed to persist position for {:?}: {}", user_addr, e);
             }
-            Some(other) => {
-                tracing::error!("Unexpected event {other:?}");
-            }
-            None => {
-                tracing::error!("Unexpected None");
+
+            let msg = ServerMessage::Position {
+                address: format!("{:?}", user_addr),
+                balance,
+                holdings,
+                block_number,
+            };
+            let _ = broadcast_tx.send(msg);
+        }
+        Some(&StockMarket::NewUser::SIGNATURE_HASH) => {
+            let event = StockMarket::NewUser::decode_log(&log.inner, true)?;
+            let user_addr = event.user;
+
+            tracing::info!("👤 New user registered: {:?}", user_addr);
+
+            let msg = ServerMessage::Position {
+                address: format!("{:?}", user_addr),
+                balance: 1000,
+                holdings: 0,
+                block_number: 0,
+            };
+            let _ = broadcast_tx.send(msg);
+        }
+        Some(&StockMarket::Started::SIGNATURE_HASH) => {
+            let event = StockMarket::Started::decode_log(&log.inner, true)?;
+            let start_block: u64 = event.startBlock.to();
+            let end_block: u64 = event.endBlock.to();
+
+            tracing::info!("🎮 Game started: blocks {} to {}", start_block, end_block);
+
+            let mut state_guard = state.write().await;
+            state_guard.reset_txs.clear();
+            state_guard.game_start_block = Some(start_block);
+            state_guard.game_end_block = Some(end_block);
+            let current_price = state_guard.current_price;
+            state_guard.price_history.clear();
+            state_guard.push_price(PricePoint {
+                block_number: start_block,
+                price: current_price,
+            });
+            if let Err(e) = state_guard
+                .store
+                .clear_game()
+                .and_then(|()| state_guard.store.record_price(start_block, current_price))
+            {
+                tracing::error!("Failed to reset game series: {}", e);
             }
+
+            let msg = ServerMessage::GameStarted {
+                start_height: start_block,
+                end_height: end_block,
+            };
+            let _ = broadcast_tx.send(msg);
+        }
+        Some(other) => {
+            tracing::error!("Unexpected event {other:?}");
+        }
+        None => {
+            tracing::error!("Unexpected None");
         }
     }
     Ok(())
 }
+
+fn publish_candles(
+    state: &AppState,
+    closed: Vec<Candle>,
+    broadcast_tx: &broadcast::Sender<ServerMessage>,
+) {
+    for candle in closed {
+        tracing::debug!(
+            "🕯️  Candle closed: {} blocks from {} (o {} h {} l {} c {} v {})",
+            candle.window,
+            candle.start_block,
+            candle.open,
+            candle.high,
+            candle.low,
+            candle.close,
+            candle.volume
+        );
+        if let Err(e) = state
+            .store
+            .insert_candle(candle.window, candle.start_block, &candle)
+        {
+            tracing::error!("Failed to persist candle: {}", e);
+        }
+        let _ = broadcast_tx.send(ServerMessage::CandleClosed { candle });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        store::Store,
+        ws::{Trade, TradeSide},
+    };
+    use alloy::primitives::{Address, B256, U256};
+
+    const ALICE: Address = Address::repeat_byte(0xa1);
+
+    fn log(event: &impl SolEvent, block: u64, tx: u8, index: u64) -> Log {
+        Log {
+            inner: alloy::primitives::Log {
+                address: Address::ZERO,
+                data: event.encode_log_data(),
+            },
+            block_number: Some(block),
+            transaction_hash: Some(B256::repeat_byte(tx)),
+            log_index: Some(index),
+            ..Default::default()
+        }
+    }
+
+    fn position(balance: u64, holdings: u64, block: u64) -> StockMarket::Position {
+        StockMarket::Position {
+            user: ALICE,
+            balance: U256::from(balance),
+            holdings: U256::from(holdings),
+            blockNumber: U256::from(block),
+        }
+    }
+
+    fn started(start: u64, end: u64) -> StockMarket::Started {
+        StockMarket::Started {
+            startBlock: U256::from(start),
+            endBlock: U256::from(end),
+        }
+    }
+
+    #[tokio::test]
+    async fn restart_mid_game_records_no_phantom_trades() {
+        let store = Arc::new(Store::temporary().unwrap());
+        let state = Arc::new(RwLock::new(AppState::new(store, &[10]).unwrap()));
+        let (broadcast_tx, _) = broadcast::channel(64);
+
+        let price = StockMarket::PriceUpdate {
+            newPrice: U256::from(50),
+            blockNumber: U256::from(30),
+        };
+        handle_log(&log(&started(10, 100), 10, 1, 0), &state, &broadcast_tx)
+            .await
+            .unwrap();
+        handle_log(
+            &log(&position(450, 11, 20), 20, 2, 0),
+            &state,
+            &broadcast_tx,
+        )
+        .await
+        .unwrap();
+
+        // The game is restarted before block 100: the backend sends reset()
+        // as tx 3, then start().
+        state.write().await.reset_txs.insert(B256::repeat_byte(3));
+        for log in [
+            log(&position(500, 10, 30), 30, 3, 0),
+            log(&price, 30, 3, 1),
+            log(&started(31, 131), 31, 4, 0),
+        ] {
+            handle_log(&log, &state, &broadcast_tx).await.unwrap();
+        }
+
+        let state_guard = state.read().await;
+        let trades: Vec<Trade> = state_guard.store.load_trades(ALICE).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].side, TradeSide::Buy);
+        assert_eq!((trades[0].quantity, trades[0].price), (1, 50));
+        assert_eq!(state_guard.balances.get(&ALICE), Some(&500));
+        assert!(state_guard.reset_txs.is_empty());
+    }
+}
@@ -0,0 +1,482 @@
+use alloy::{
+    primitives::{Address, B256, U256},
+    signers::local::PrivateKeySigner,
+};
+use anyhow::{Context, Result, bail};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_CONFIG_PATH: &str = "monomarket.toml";
+
+/// Command line flags. Every flag can also be set through the environment
+/// variable next to it; both take precedence over the config file.
+#[derive(Debug, Parser)]
+#[command(version, about = "Monomarket game server", long_about = None)]
+pub struct Cli {
+    /// Path to the TOML config file (defaults to ./monomarket.toml if present)
+    #[arg(long, short, env = "MONOMARKET_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Print the effective configuration and exit
+    #[arg(long)]
+    pub print_config: bool,
+
+    #[arg(long, env = "RPC_URL")]
+    pub rpc_url: Option<String>,
+
+    #[arg(long, env = "CONTRACT_ADDRESS")]
+    pub contract_address: Option<Address>,
+
+    #[arg(long, env = "PRIVATE_KEY", hide_env_values = true)]
+    pub private_key: Option<String>,
+
+    #[arg(long, env = "WEBSOCKET_PORT")]
+    pub port: Option<u16>,
+
+    #[arg(long, env = "STATIC_DIR")]
+    pub static_dir: Option<PathBuf>,
+
+    #[arg(long, env = "STATE_DB")]
+    pub state_db: Option<PathBuf>,
+
+    #[arg(long, env = "BACKFILL_FROM_BLOCK")]
+    pub backfill_from_block: Option<u64>,
+
+    #[arg(long, env = "GAME_DURATION")]
+    pub game_duration: Option<u64>,
+
+    /// Bearer token that unlocks admin commands such as restart_game
+    #[arg(long, env = "ADMIN_TOKEN", hide_env_values = true)]
+    pub admin_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub rpc_url: String,
+    pub contract_address: Address,
+    pub private_key: String,
+    pub port: u16,
+    pub static_dir: PathBuf,
+    pub state_db: PathBuf,
+    pub backfill_from_block: Option<u64>,
+    pub faucet: FaucetConfig,
+    pub gas: GasConfig,
+    pub fees: FeeConfig,
+    pub game: GameConfig,
+    pub candles: CandleConfig,
+    pub names: NameConfig,
+    pub admin: AdminConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FaucetConfig {
+    /// Amount sent to a new player
+    #[serde(with = "mon")]
+    pub amount: U256,
+    /// On restart, players below this balance are topped back up to `amount`
+    #[serde(with = "mon")]
+    pub refund_threshold: U256,
+    /// New addresses funded per client IP per rolling hour
+    pub per_ip_per_hour: u32,
+    /// Funding requests allowed on a single websocket connection
+    pub per_connection: u32,
+    /// Total handed to new addresses per rolling hour
+    #[serde(with = "mon")]
+    pub hourly_budget: U256,
+    /// Leading zero bits required in the proof-of-work, 0 disables it
+    pub pow_difficulty: u8,
+    /// Reverse proxies in front of the server, each appending the address it
+    /// saw to X-Forwarded-For. The client IP is read that many entries from
+    /// the right; 0 ignores the header.
+    pub trusted_proxies: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GasConfig {
+    /// Static fees, used when eth_feeHistory is unavailable
+    pub max_fee_per_gas: u64,
+    pub max_priority_fee_per_gas: u64,
+    pub fund_gas_limit: u64,
+    pub tick_gas_limit: u64,
+    /// Used for the owner-only reset() and start() calls
+    pub admin_gas_limit: u64,
+    /// Headroom added on top of eth_estimateGas for the limits sent to clients
+    pub estimate_margin_percent: u64,
+    pub estimate_interval_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeeConfig {
+    /// Hard cap on max fee per gas for any backend tx, including bumps
+    pub ceiling: u64,
+    pub min_priority_fee: u64,
+    /// Number of recent blocks sampled from eth_feeHistory
+    pub history_blocks: u64,
+    /// Reward percentile used as the tip for ticks and admin txs
+    pub urgent_percentile: f64,
+    /// Reward percentile used as the tip for funding txs
+    pub relaxed_percentile: f64,
+    /// Pending txs older than this are re-sent with higher fees
+    pub bump_after_secs: u64,
+    pub bump_percent: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    /// Length of a game started by restart, in blocks
+    pub duration: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CandleConfig {
+    /// Candle sizes in blocks
+    pub windows: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NameConfig {
+    /// Length bounds for display names, in characters
+    pub min_len: usize,
+    pub max_len: usize,
+    /// Names containing any of these (case-insensitive) are rejected
+    pub blocklist: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Sent by admin clients as `Authorization: Bearer <token>` or an
+    /// `ADMIN_TOKEN` cookie when opening /ws. Admin commands are disabled
+    /// when unset.
+    pub token: Option<String>,
+    /// Origins besides the server's own whose websockets may authenticate
+    /// with the cookie, e.g. a frontend served from another host
+    pub allowed_origins: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            rpc_url: String::new(),
+            contract_address: Address::ZERO,
+            private_key: String::new(),
+            port: 8000,
+            static_dir: PathBuf::from("frontend/Monomarket/dist"),
+            state_db: PathBuf::from("monomarket.db"),
+            backfill_from_block: None,
+            faucet: FaucetConfig::default(),
+            gas: GasConfig::default(),
+            fees: FeeConfig::default(),
+            game: GameConfig::default(),
+            candles: CandleConfig::default(),
+            names: NameConfig::default(),
+            admin: AdminConfig::default(),
+        }
+    }
+}
+
+impl Default for FaucetConfig {
+    fn default() -> Self {
+        Self {
+            amount: U256::from(500_000_000_000_000_000u64), // 0.5 MON
+            refund_threshold: U256::from(450_000_000_000_000_000u64), // 0.45 MON
+            per_ip_per_hour: 3,
+            per_connection: 2,
+            hourly_budget: U256::from(10_000_000_000_000_000_000u64), // 10 MON
+            pow_difficulty: 0,
+            trusted_proxies: 0,
+        }
+    }
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self {
+            max_fee_per_gas: 0x21d664903c,
+            max_priority_fee_per_gas: 1_000_000_000,
+            fund_gas_limit: 25_000, // experimentally obtained 25k gas
+            tick_gas_limit: 60_000,
+            admin_gas_limit: 500_000,
+            estimate_margin_percent: 20,
+            estimate_interval_secs: 60,
+        }
+    }
+}
+
+impl Default for FeeConfig {
+    fn default() -> Self {
+        Self {
+            ceiling: 300_000_000_000, // 300 gwei
+            min_priority_fee: 1_000_000_000,
+            history_blocks: 10,
+            urgent_percentile: 90.0,
+            relaxed_percentile: 25.0,
+            bump_after_secs: 5,
+            bump_percent: 25,
+        }
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self { duration: 200 }
+    }
+}
+
+impl Default for CandleConfig {
+    fn default() -> Self {
+        Self {
+            windows: vec![10, 50],
+        }
+    }
+}
+
+impl Default for NameConfig {
+    fn default() -> Self {
+        Self {
+            min_len: 3,
+            max_len: 20,
+            blocklist: Vec::new(),
+        }
+    }
+}
+
+/// Native token amounts are written as decimal MON strings ("0.5"); plain
+/// integers are read as wei.
+mod mon {
+    use alloy::primitives::{
+        U256,
+        utils::{format_ether, parse_ether},
+    };
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Amount {
+        Wei(u64),
+        Mon(String),
+    }
+
+    pub fn serialize<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_ether(*value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+        match Amount::deserialize(deserializer)? {
+            Amount::Wei(wei) => Ok(U256::from(wei)),
+            Amount::Mon(mon) => parse_ether(mon.trim())
+                .map_err(|e| D::Error::custom(format!("invalid MON amount '{}': {}", mon, e))),
+        }
+    }
+}
+
+impl Config {
+    /// Builds the effective config: defaults, then the TOML file, then
+    /// environment variables and flags.
+    pub fn load(cli: &Cli) -> Result<Self> {
+        let mut config = match &cli.config {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_PATH))?
+            }
+            None => Self::default(),
+        };
+
+        if let Some(rpc_url) = &cli.rpc_url {
+            config.rpc_url = rpc_url.clone();
+        }
+        if let Some(contract_address) = cli.contract_address {
+            config.contract_address = contract_address;
+        }
+        if let Some(private_key) = &cli.private_key {
+            config.private_key = private_key.clone();
+        }
+        if let Some(port) = cli.port {
+            config.port = port;
+        }
+        if let Some(static_dir) = &cli.static_dir {
+            config.static_dir = static_dir.clone();
+        }
+        if let Some(state_db) = &cli.state_db {
+            config.state_db = state_db.clone();
+        }
+        if let Some(block) = cli.backfill_from_block {
+            config.backfill_from_block = Some(block);
+        }
+        if let Some(duration) = cli.game_duration {
+            config.game.duration = duration;
+        }
+        if let Some(token) = &cli.admin_token {
+            config.admin.token = Some(token.clone());
+        }
+
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.rpc_url.starts_with("ws://") || self.rpc_url.starts_with("wss://")) {
+            bail!(
+                "rpc_url must be a ws:// or wss:// URL, got '{}'",
+                self.rpc_url
+            );
+        }
+        if self.contract_address == Address::ZERO {
+            bail!("contract_address is not set");
+        }
+        self.signer().context("private_key is missing or invalid")?;
+        if self.faucet.amount.is_zero() {
+            bail!("faucet.amount must be greater than 0");
+        }
+        if self.faucet.refund_threshold > self.faucet.amount {
+            bail!(
+                "faucet.refund_threshold ({}) must not exceed faucet.amount ({})",
+                self.faucet.refund_threshold,
+                self.faucet.amount
+            );
+        }
+        if self.faucet.hourly_budget < self.faucet.amount {
+            bail!("faucet.hourly_budget must be at least faucet.amount");
+        }
+        // The faucet adds one more `amount` to what was spent this hour.
+        if self
+            .faucet
+            .hourly_budget
+            .checked_add(self.faucet.amount)
+            .is_none()
+        {
+            bail!("faucet.hourly_budget is too large");
+        }
+        if self.faucet.pow_difficulty > 32 {
+            bail!("faucet.pow_difficulty must be at most 32");
+        }
+        if self.gas.max_priority_fee_per_gas > self.gas.max_fee_per_gas {
+            bail!("gas.max_priority_fee_per_gas must not exceed gas.max_fee_per_gas");
+        }
+        if self.gas.fund_gas_limit < 21_000 {
+            bail!("gas.fund_gas_limit must be at least 21000");
+        }
+        if self.gas.tick_gas_limit == 0 || self.gas.admin_gas_limit == 0 {
+            bail!("gas limits must be greater than 0");
+        }
+        if self.gas.estimate_interval_secs == 0 {
+            bail!("gas.estimate_interval_secs must be greater than 0");
+        }
+        if self.fees.ceiling < self.gas.max_fee_per_gas {
+            bail!("fees.ceiling must be at least gas.max_fee_per_gas");
+        }
+        if self.fees.min_priority_fee > self.fees.ceiling {
+            bail!("fees.min_priority_fee must not exceed fees.ceiling");
+        }
+        if self.fees.history_blocks == 0 || self.fees.history_blocks > 1024 {
+            bail!("fees.history_blocks must be between 1 and 1024");
+        }
+        for percentile in [self.fees.urgent_percentile, self.fees.relaxed_percentile] {
+            if !(0.0..=100.0).contains(&percentile) {
+                bail!(
+                    "fee percentiles must be between 0 and 100, got {}",
+                    percentile
+                );
+            }
+        }
+        if self.fees.relaxed_percentile > self.fees.urgent_percentile {
+            bail!("fees.relaxed_percentile must not exceed fees.urgent_percentile");
+        }
+        if self.fees.bump_percent < 10 {
+            bail!("fees.bump_percent must be at least 10, nodes reject smaller replacements");
+        }
+        if self.game.duration == 0 {
+            bail!("game.duration must be greater than 0");
+        }
+        if self.candles.windows.contains(&0) {
+            bail!("candles.windows must all be greater than 0");
+        }
+        if self.names.min_len == 0 || self.names.min_len > self.names.max_len {
+            bail!("names.min_len must be between 1 and names.max_len");
+        }
+        if let Some(token) = &self.admin.token {
+            if token.len() < 16 {
+                bail!("admin.token must be at least 16 characters");
+            }
+            if !token
+                .bytes()
+                .all(|b| b.is_ascii_graphic() && !b"\";,\\".contains(&b))
+            {
+                bail!(
+                    "admin.token must be printable ASCII without quotes, commas, semicolons or backslashes"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn signer(&self) -> Result<PrivateKeySigner> {
+        let key: B256 = self.private_key.parse()?;
+        Ok(PrivateKeySigner::from_bytes(&key)?)
+    }
+
+    /// TOML rendering of the config with the private key and admin token masked.
+    pub fn to_redacted_toml(&self) -> Result<String> {
+        let mut redacted = self.clone();
+        if !redacted.private_key.is_empty() {
+            redacted.private_key = "<redacted>".to_string();
+        }
+        if redacted.admin.token.is_some() {
+            redacted.admin.token = Some("<redacted>".to_string());
+        }
+        Ok(toml::to_string_pretty(&redacted)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_config_parses() {
+        let config = Config::from_file(Path::new("monomarket.example.toml")).unwrap();
+        assert_eq!(config.faucet.amount, U256::from(500_000_000_000_000_000u64));
+        assert_eq!(
+            config.faucet.hourly_budget,
+            U256::from(10_000_000_000_000_000_000u64)
+        );
+    }
+
+    #[test]
+    fn faucet_amounts_above_u64_wei() {
+        let config: Config = toml::from_str(
+            r#"
+            [faucet]
+            amount = "2"
+            hourly_budget = "1000"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.faucet.hourly_budget,
+            U256::from(1000u64) * U256::from(10u64).pow(U256::from(18))
+        );
+        assert!(toml::from_str::<Config>("[faucet]\namount = \"0.5 MON\"").is_err());
+
+        let mut config = config;
+        config.faucet.hourly_budget = U256::MAX;
+        config.rpc_url = "ws://localhost".to_string();
+        config.contract_address = Address::repeat_byte(1);
+        config.private_key = format!("{:?}", B256::repeat_byte(1));
+        assert!(config.validate().is_err());
+    }
+}
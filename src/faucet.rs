@@ -0,0 +1,233 @@
+use crate::{config::FaucetConfig, store::Store, ws::FundErrorReason};
+use alloy::primitives::{Address, B256, U256, keccak256};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+const WINDOW: Duration = Duration::from_secs(3600);
+
+#[derive(Debug)]
+pub struct FundRefusal {
+    pub reason: FundErrorReason,
+    pub message: String,
+}
+
+impl FundRefusal {
+    pub fn new(reason: FundErrorReason, message: impl Into<String>) -> Self {
+        Self {
+            reason,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    by_ip: HashMap<IpAddr, VecDeque<Instant>>,
+    spent: VecDeque<(Instant, U256)>,
+    /// Authorized but not yet confirmed, so the same address can't be funded
+    /// twice while its first tx is pending.
+    in_flight: HashSet<Address>,
+}
+
+/// Decides whether a first-time funding may go out. Limits are per IP and
+/// global per rolling hour; the ledger of funded addresses is persisted so a
+/// restart doesn't hand out a second round.
+pub struct Faucet {
+    config: FaucetConfig,
+    store: Arc<Store>,
+    inner: Mutex<Inner>,
+}
+
+impl Faucet {
+    pub fn new(config: FaucetConfig, store: Arc<Store>) -> Self {
+        Self {
+            config,
+            store,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Checks every limit and, if they all pass, reserves `amount` against
+    /// them.
+    pub async fn authorize(
+        &self,
+        address: Address,
+        ip: IpAddr,
+        amount: U256,
+    ) -> Result<(), FundRefusal> {
+        match self.store.is_funded(address) {
+            Ok(false) => {}
+            Ok(true) => {
+                return Err(FundRefusal::new(
+                    FundErrorReason::AlreadyFunded,
+                    format!("{:?} has already been funded", address),
+                ));
+            }
+            Err(e) => {
+                tracing::error!("Failed to read faucet ledger: {}", e);
+                return Err(FundRefusal::new(
+                    FundErrorReason::Unavailable,
+                    "Faucet ledger unavailable",
+                ));
+            }
+        }
+
+        let now = Instant::now();
+        let mut inner = self.inner.lock().await;
+        inner.prune(now);
+
+        if inner.in_flight.contains(&address) {
+            return Err(FundRefusal::new(
+                FundErrorReason::AlreadyFunded,
+                format!("{:?} is already being funded", address),
+            ));
+        }
+
+        let ip_count = inner.by_ip.get(&ip).map_or(0, VecDeque::len);
+        if ip_count >= self.config.per_ip_per_hour as usize {
+            return Err(FundRefusal::new(
+                FundErrorReason::IpLimit,
+                format!(
+                    "At most {} addresses can be funded per hour from one IP",
+                    self.config.per_ip_per_hour
+                ),
+            ));
+        }
+
+        let spent = inner
+            .spent
+            .iter()
+            .fold(U256::ZERO, |sum, (_, amount)| sum.saturating_add(*amount));
+        if spent.saturating_add(amount) > self.config.hourly_budget {
+            return Err(FundRefusal::new(
+                FundErrorReason::BudgetExhausted,
+                "The faucet's hourly budget is used up, try again later",
+            ));
+        }
+
+        inner.by_ip.entry(ip).or_default().push_back(now);
+        inner.spent.push_back((now, amount));
+        inner.in_flight.insert(address);
+        Ok(())
+    }
+
+    /// Adds `address` to the ledger once its funding tx has landed.
+    pub async fn record(&self, address: Address, amount: U256) {
+        if let Err(e) = self.store.record_funding(address, amount) {
+            tracing::error!("Failed to record funding of {:?}: {}", address, e);
+        }
+        self.inner.lock().await.in_flight.remove(&address);
+    }
+
+    /// Lets `address` ask again after its funding tx failed. The IP and budget
+    /// reservations stay, so failures still count against the limits.
+    pub async fn release(&self, address: Address) {
+        self.inner.lock().await.in_flight.remove(&address);
+    }
+
+    pub fn pow_difficulty(&self) -> u8 {
+        self.config.pow_difficulty
+    }
+
+    pub fn per_connection(&self) -> u32 {
+        self.config.per_connection
+    }
+}
+
+impl Inner {
+    fn prune(&mut self, now: Instant) {
+        let expired = |at: &Instant| now.duration_since(*at) >= WINDOW;
+        self.by_ip.retain(|_, times| {
+            while times.front().is_some_and(expired) {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+        while self.spent.front().is_some_and(|(at, _)| expired(at)) {
+            self.spent.pop_front();
+        }
+    }
+}
+
+/// A solution is valid when `keccak256(challenge ‖ address ‖ solution)`, with
+/// `solution` as 8 big-endian bytes, starts with `difficulty` zero bits.
+pub fn verify_pow(challenge: B256, address: Address, solution: u64, difficulty: u8) -> bool {
+    leading_zero_bits(&pow_hash(challenge, address, solution)) >= u32::from(difficulty)
+}
+
+fn pow_hash(challenge: B256, address: Address, solution: u64) -> B256 {
+    let mut preimage = [0u8; 60];
+    preimage[..32].copy_from_slice(challenge.as_slice());
+    preimage[32..52].copy_from_slice(address.as_slice());
+    preimage[52..].copy_from_slice(&solution.to_be_bytes());
+    keccak256(preimage)
+}
+
+fn leading_zero_bits(hash: &B256) -> u32 {
+    let mut bits = 0;
+    for byte in hash.iter() {
+        if *byte != 0 {
+            return bits + byte.leading_zeros();
+        }
+        bits += 8;
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: Address = Address::repeat_byte(0xa1);
+
+    fn zero_bits(challenge: B256, address: Address, solution: u64) -> u32 {
+        leading_zero_bits(&pow_hash(challenge, address, solution))
+    }
+
+    #[test]
+    fn leading_zero_bits_cross_byte_boundaries() {
+        let mut hash = B256::ZERO;
+        assert_eq!(leading_zero_bits(&hash), 256);
+        hash[0] = 0x80;
+        assert_eq!(leading_zero_bits(&hash), 0);
+        hash[0] = 0x01;
+        assert_eq!(leading_zero_bits(&hash), 7);
+        hash[0] = 0;
+        hash[1] = 0x10;
+        assert_eq!(leading_zero_bits(&hash), 11);
+    }
+
+    #[test]
+    fn solution_meets_exactly_its_difficulty() {
+        let challenge = B256::repeat_byte(7);
+        let solution = (0..)
+            .find(|&s| zero_bits(challenge, ALICE, s) >= 8)
+            .unwrap();
+        let bits = zero_bits(challenge, ALICE, solution) as u8;
+
+        assert!(verify_pow(challenge, ALICE, solution, 0));
+        assert!(verify_pow(challenge, ALICE, solution, bits));
+        assert!(!verify_pow(challenge, ALICE, solution, bits + 1));
+    }
+
+    #[test]
+    fn solution_is_bound_to_challenge_and_address() {
+        let challenge = B256::repeat_byte(7);
+        let solution = (0..)
+            .find(|&s| {
+                zero_bits(challenge, ALICE, s) >= 8
+                    && zero_bits(B256::repeat_byte(8), ALICE, s) < 8
+                    && zero_bits(challenge, Address::ZERO, s) < 8
+            })
+            .unwrap();
+
+        assert!(verify_pow(challenge, ALICE, solution, 8));
+        assert!(!verify_pow(B256::repeat_byte(8), ALICE, solution, 8));
+        assert!(!verify_pow(challenge, Address::ZERO, solution, 8));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{
+    config::{Config, FeeConfig},
+    nonce::NonceManager,
+};
+use alloy::{
+    eips::BlockNumberOrTag,
+    network::TransactionBuilder,
+    providers::{Provider, WalletProvider},
+    rpc::types::TransactionRequest,
+    transports::Transport,
+};
+use anyhow::Result;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+// Fee history is shared by every tx sent within this window.
+const CACHE_TTL: Duration = Duration::from_secs(1);
+
+/// How quickly a backend tx needs to land. Ticks are only valid in the block
+/// they target, funding can wait a few blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxClass {
+    /// Priced like `Urgent`, but never bumped: by the time a tick is stale
+    /// its block has passed and the next tick supersedes it.
+    Tick,
+    Urgent,
+    Relaxed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fees {
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+impl Fees {
+    pub fn apply(self, tx: TransactionRequest) -> TransactionRequest {
+        tx.with_max_fee_per_gas(self.max_fee_per_gas)
+            .with_max_priority_fee_per_gas(self.max_priority_fee_per_gas)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Snapshot {
+    next_base_fee: u128,
+    urgent_tip: u128,
+    relaxed_tip: u128,
+}
+
+/// EIP-1559 fee suggestions from `eth_feeHistory`, capped at `fees.ceiling`.
+/// Falls back to the static `gas.*` fees if the node can't answer.
+pub struct FeeOracle {
+    config: Arc<Config>,
+    cache: Mutex<Option<(Instant, Snapshot)>>,
+}
+
+impl FeeOracle {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            config,
+            cache: Mutex::new(None),
+        }
+    }
+
+    pub fn fallback(&self) -> Fees {
+        Fees {
+            max_fee_per_gas: self.config.gas.max_fee_per_gas.into(),
+            max_priority_fee_per_gas: self.config.gas.max_priority_fee_per_gas.into(),
+        }
+    }
+
+    pub async fn fees<T, P>(&self, provider: &P, class: TxClass) -> Fees
+    where
+        T: Transport + Clone,
+        P: Provider<T>,
+    {
+        match self.snapshot(provider).await {
+            Ok(snapshot) => self.price(snapshot, class),
+            Err(e) => {
+                tracing::warn!("⚠️  Fee history unavailable, using static fees: {}", e);
+                self.fallback()
+            }
+        }
+    }
+
+    fn price(&self, snapshot: Snapshot, class: TxClass) -> Fees {
+        let fees = &self.config.fees;
+        let ceiling = u128::from(fees.ceiling);
+        // Headroom for the base fee rising before inclusion.
+        let (tip, base) = match class {
+            TxClass::Tick | TxClass::Urgent => (snapshot.urgent_tip, snapshot.next_base_fee * 2),
+            TxClass::Relaxed => (snapshot.relaxed_tip, snapshot.next_base_fee * 5 / 4),
+        };
+        let tip = tip.max(fees.min_priority_fee.into());
+        let max_fee = (base + tip).min(ceiling);
+        Fees {
+            max_fee_per_gas: max_fee,
+            max_priority_fee_per_gas: tip.min(max_fee),
+        }
+    }
+
+    async fn snapshot<T, P>(&self, provider: &P) -> Result<Snapshot>
+    where
+        T: Transport + Clone,
+        P: Provider<T>,
+    {
+        let mut cache = self.cache.lock().await;
+        if let Some((fetched_at, snapshot)) = *cache
+            && fetched_at.elapsed() < CACHE_TTL
+        {
+            return Ok(snapshot);
+        }
+
+        let fees = &self.config.fees;
+        let history = provider
+            .get_fee_history(
+                fees.history_blocks,
+                BlockNumberOrTag::Latest,
+                &[fees.relaxed_percentile, fees.urgent_percentile],
+            )
+            .await?;
+
+        let next_base_fee = history
+            .next_block_base_fee()
+            .ok_or_else(|| anyhow::anyhow!("eth_feeHistory returned no base fee"))?;
+        let rewards = history.reward.unwrap_or_default();
+        let snapshot = Snapshot {
+            next_base_fee,
+            relaxed_tip: median(rewards.iter().filter_map(|r| r.first().copied())),
+            urgent_tip: median(rewards.iter().filter_map(|r| r.get(1).copied())),
+        };
+        tracing::debug!("Fee snapshot: {:?}", snapshot);
+
+        *cache = Some((Instant::now(), snapshot));
+        Ok(snapshot)
+    }
+}
+
+fn median(values: impl Iterator<Item = u128>) -> u128 {
+    let mut values: Vec<u128> = values.collect();
+    if values.is_empty() {
+        return 0;
+    }
+    values.sort_unstable();
+    values[values.len() / 2]
+}
+
+/// Fees for re-sending a tx that was sent with `old`: each raised by
+/// `bump_percent`, or to the `current` suggestion if that is higher, and
+/// clamped to the ceiling.
+fn bumped(config: &FeeConfig, old: Fees, current: Fees) -> Fees {
+    let bump = |fee: u128| fee * (100 + u128::from(config.bump_percent)) / 100;
+    let max_fee = bump(old.max_fee_per_gas)
+        .max(current.max_fee_per_gas)
+        .min(config.ceiling.into());
+    Fees {
+        max_fee_per_gas: max_fee,
+        max_priority_fee_per_gas: bump(old.max_priority_fee_per_gas)
+            .max(current.max_priority_fee_per_gas)
+            .min(max_fee),
+    }
+}
+
+/// Re-broadcasts backend txs that have been pending longer than
+/// `fees.bump_after_secs` with fees raised by at least `fees.bump_percent`
+/// (nodes reject replacements below a ~10% bump), never above the ceiling.
+/// Ticks are left alone.
+pub async fn run_fee_bumper<T, P>(
+    provider: P,
+    nonces: Arc<NonceManager>,
+    oracle: Arc<FeeOracle>,
+    config: Arc<Config>,
+) where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider,
+{
+    let bump_after = Duration::from_secs(config.fees.bump_after_secs);
+    let ceiling = u128::from(config.fees.ceiling);
+
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+
+        if nonces.stale(bump_after).await.is_empty() {
+            continue;
+        }
+        // Drop anything that was mined since the last resync before bumping.
+        if let Err(e) = nonces.resync(&provider).await {
+            tracing::error!("Nonce resync failed: {}", e);
+            continue;
+        }
+
+        for (nonce, pending) in nonces.stale(bump_after).await {
+            if pending.class == TxClass::Tick {
+                continue;
+            }
+            let (Some(max_fee_per_gas), Some(max_priority_fee_per_gas)) = (
+                pending.request.max_fee_per_gas,
+                pending.request.max_priority_fee_per_gas,
+            ) else {
+                continue;
+            };
+            if max_fee_per_gas >= ceiling {
+                tracing::warn!(
+                    "⚠️  Tx {:?} (nonce {}) stuck at the fee ceiling",
+                    pending.tx_hash,
+                    nonce
+                );
+                continue;
+            }
+
+            let old = Fees {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            };
+            let current = oracle.fees(&provider, TxClass::Urgent).await;
+            let fees = bumped(&config.fees, old, current);
+
+            match nonces
+                .replace(&provider, nonce, fees.apply(pending.request))
+                .await
+            {
+                Ok(tx_hash) => tracing::info!(
+                    "⏫ Bumped nonce {} to {} max fee: {:?} -> {:?}",
+                    nonce,
+                    fees.max_fee_per_gas,
+                    pending.tx_hash,
+                    tx_hash
+                ),
+                Err(e) => tracing::warn!("⚠️  Failed to bump nonce {}: {}", nonce, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GWEI: u128 = 1_000_000_000;
+
+    fn oracle() -> FeeOracle {
+        let mut config = Config::default();
+        config.fees.ceiling = 100 * GWEI as u64;
+        config.fees.min_priority_fee = GWEI as u64;
+        config.fees.bump_percent = 25;
+        FeeOracle::new(Arc::new(config))
+    }
+
+    fn fees(max_fee: u128, tip: u128) -> Fees {
+        Fees {
+            max_fee_per_gas: max_fee * GWEI,
+            max_priority_fee_per_gas: tip * GWEI,
+        }
+    }
+
+    fn snapshot(base: u128, relaxed: u128, urgent: u128) -> Snapshot {
+        Snapshot {
+            next_base_fee: base * GWEI,
+            relaxed_tip: relaxed * GWEI,
+            urgent_tip: urgent * GWEI,
+        }
+    }
+
+    #[test]
+    fn classes_get_their_base_fee_headroom_and_tip() {
+        let oracle = oracle();
+        let snapshot = snapshot(20, 2, 5);
+        assert_eq!(oracle.price(snapshot, TxClass::Urgent), fees(45, 5));
+        assert_eq!(oracle.price(snapshot, TxClass::Relaxed), fees(27, 2));
+    }
+
+    #[test]
+    fn tip_is_at_least_the_minimum() {
+        let oracle = oracle();
+        assert_eq!(
+            oracle.price(snapshot(20, 0, 0), TxClass::Relaxed),
+            fees(26, 1)
+        );
+    }
+
+    #[test]
+    fn fees_are_clamped_to_the_ceiling() {
+        let oracle = oracle();
+        assert_eq!(
+            oracle.price(snapshot(60, 2, 5), TxClass::Urgent),
+            fees(100, 5)
+        );
+        assert_eq!(
+            oracle.price(snapshot(60, 2, 150), TxClass::Urgent),
+            fees(100, 100)
+        );
+    }
+
+    #[test]
+    fn bumps_raise_by_the_percentage_or_to_the_current_fees() {
+        let config = &oracle().config.fees;
+        assert_eq!(bumped(config, fees(40, 4), fees(30, 2)), fees(50, 5));
+        assert_eq!(bumped(config, fees(40, 4), fees(60, 8)), fees(60, 8));
+        assert_eq!(bumped(config, fees(90, 4), fees(30, 2)), fees(100, 5));
+    }
+
+    #[test]
+    fn median_picks_the_upper_middle() {
+        assert_eq!(median([3, 1, 2].into_iter()), 2);
+        assert_eq!(median([4, 1, 3, 2].into_iter()), 3);
+        assert_eq!(median(std::iter::empty()), 0);
+    }
+}
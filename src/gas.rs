@@ -0,0 +1,153 @@
+use crate::{
+    AppState, GasCosts,
+    backend::StockMarket,
+    config::Config,
+    ws::{GasInfo, ServerMessage},
+};
+use alloy::{
+    primitives::{Address, U256},
+    providers::Provider,
+    signers::local::PrivateKeySigner,
+    transports::Transport,
+};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::{RwLock, broadcast};
+
+/// Used until the first successful estimate, and for any call that can't be
+/// estimated (e.g. buy/sell before anyone has registered).
+pub const FALLBACK_GAS_COSTS: GasCosts = GasCosts {
+    register: 115_000,
+    buy: 39_733,
+    sell: 39_733,
+};
+
+/// Runs `eth_estimateGas` for `register()`, `buy(1)` and `sell(1)` and pads
+/// each result by `margin_percent`. Calls that revert keep their `previous`
+/// value.
+pub async fn estimate_gas_costs<T, P>(
+    provider: &P,
+    contract_addr: Address,
+    state: &Arc<RwLock<AppState>>,
+    margin_percent: u64,
+    previous: &GasCosts,
+) -> GasCosts
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let contract = StockMarket::new(contract_addr, provider);
+    let with_margin = |gas: u64| gas * (100 + margin_percent) / 100;
+
+    // A fresh address pays for the first-time registration storage writes.
+    let newcomer = PrivateKeySigner::random().address();
+    let register = match contract.register().from(newcomer).estimate_gas().await {
+        Ok(gas) => with_margin(gas),
+        Err(e) => {
+            tracing::warn!("⚠️  Could not estimate register gas: {}", e);
+            previous.register
+        }
+    };
+
+    // buy/sell only succeed for a registered player that can afford them.
+    let trader = {
+        let state_guard = state.read().await;
+        let price = state_guard.current_price;
+        state_guard
+            .balances
+            .iter()
+            .find(|(address, balance)| {
+                **balance >= price && state_guard.holdings.get(*address).copied().unwrap_or(0) > 0
+            })
+            .map(|(address, _)| *address)
+    };
+
+    let (buy, sell) = match trader {
+        Some(trader) => {
+            let buy = match contract
+                .buy(U256::from(1))
+                .from(trader)
+                .estimate_gas()
+                .await
+            {
+                Ok(gas) => with_margin(gas),
+                Err(e) => {
+                    tracing::debug!("Could not estimate buy gas: {}", e);
+                    previous.buy
+                }
+            };
+            let sell = match contract
+                .sell(U256::from(1))
+                .from(trader)
+                .estimate_gas()
+                .await
+            {
+                Ok(gas) => with_margin(gas),
+                Err(e) => {
+                    tracing::debug!("Could not estimate sell gas: {}", e);
+                    previous.sell
+                }
+            };
+            (buy, sell)
+        }
+        None => (previous.buy, previous.sell),
+    };
+
+    GasCosts {
+        register,
+        buy,
+        sell,
+    }
+}
+
+/// Re-estimates gas costs every `gas.estimate_interval_secs` and pushes a
+/// `GasCosts` message to clients whenever they change.
+pub async fn run_gas_estimator<T, P>(
+    provider: P,
+    contract_addr: Address,
+    state: Arc<RwLock<AppState>>,
+    gas_costs: Arc<RwLock<GasCosts>>,
+    broadcast_tx: broadcast::Sender<ServerMessage>,
+    config: Arc<Config>,
+) where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(config.gas.estimate_interval_secs));
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+
+        let previous = gas_costs.read().await.clone();
+        let updated = estimate_gas_costs(
+            &provider,
+            contract_addr,
+            &state,
+            config.gas.estimate_margin_percent,
+            &previous,
+        )
+        .await;
+        if updated == previous {
+            continue;
+        }
+
+        tracing::info!(
+            "⛽ Gas costs changed: register {} -> {}, buy {} -> {}, sell {} -> {}",
+            previous.register,
+            updated.register,
+            previous.buy,
+            updated.buy,
+            previous.sell,
+            updated.sell
+        );
+        let msg = ServerMessage::GasCosts {
+            gas_costs: GasInfo {
+                register: updated.register,
+                buy: updated.buy,
+                sell: updated.sell,
+            },
+        };
+        *gas_costs.write().await = updated;
+        let _ = broadcast_tx.send(msg);
+    }
+}
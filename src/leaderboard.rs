@@ -0,0 +1,84 @@
+use crate::{
+    AppState,
+    ws::{LeaderboardEntry, ServerMessage},
+};
+use alloy::primitives::Address;
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::{RwLock, broadcast},
+    time::MissedTickBehavior,
+};
+
+// At most one leaderboard broadcast per interval, however busy trading gets.
+const THROTTLE: Duration = Duration::from_secs(1);
+
+/// Ranks every player with a position by `balance + holdings * price`, the
+/// same formula as the contract's `getNetWorth`. Equal net worth shares a rank.
+pub fn compute(state: &AppState) -> Vec<LeaderboardEntry> {
+    ranked(state).into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// `compute`, keeping each entry's parsed address alongside it.
+pub fn ranked(state: &AppState) -> Vec<(Address, LeaderboardEntry)> {
+    let mut entries: Vec<(Address, LeaderboardEntry)> = state
+        .balances
+        .iter()
+        .map(|(address, balance)| {
+            let holdings = state.holdings.get(address).copied().unwrap_or(0);
+            let entry = LeaderboardEntry {
+                address: format!("{:?}", address),
+                name: state.names.get(address).cloned(),
+                net_worth: balance.saturating_add(holdings.saturating_mul(state.current_price)),
+                rank: 0,
+            };
+            (*address, entry)
+        })
+        .collect();
+    entries
+        .sort_by(|(a_addr, a), (b_addr, b)| b.net_worth.cmp(&a.net_worth).then(a_addr.cmp(b_addr)));
+
+    let mut rank = 0;
+    let mut previous = None;
+    for (i, (_, entry)) in entries.iter_mut().enumerate() {
+        if previous != Some(entry.net_worth) {
+            rank = i as u32 + 1;
+            previous = Some(entry.net_worth);
+        }
+        entry.rank = rank;
+    }
+    entries
+}
+
+/// Watches the broadcast stream and republishes the leaderboard whenever a
+/// price or position changed, throttled to `THROTTLE`. Final standings are
+/// sent when the game is archived, not from here.
+pub async fn run_leaderboard(
+    state: Arc<RwLock<AppState>>,
+    broadcast_tx: broadcast::Sender<ServerMessage>,
+) {
+    let mut rx = broadcast_tx.subscribe();
+    let mut interval = tokio::time::interval(THROTTLE);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut dirty = false;
+
+    loop {
+        tokio::select! {
+            msg = rx.recv() => match msg {
+                Ok(ServerMessage::PriceUpdate { .. } | ServerMessage::Position { .. }) => {
+                    dirty = true;
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("⚠️  Leaderboard lagged, skipped {} messages", skipped);
+                    dirty = true;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = interval.tick(), if dirty => {
+                let entries = compute(&*state.read().await);
+                let _ = broadcast_tx.send(ServerMessage::Leaderboard { entries });
+                dirty = false;
+            }
+        }
+    }
+}
@@ -0,0 +1,496 @@
+mod admin;
+mod api;
+mod archive;
+mod backend;
+mod backfill;
+mod candles;
+mod chain;
+mod chain_events;
+pub mod config;
+mod faucet;
+mod fees;
+mod gas;
+mod leaderboard;
+mod metrics;
+mod names;
+mod nonce;
+mod ownership;
+mod preflight;
+mod store;
+mod subscription;
+mod supervisor;
+mod trades;
+mod tx_watch;
+pub mod ws;
+mod ws_axum;
+
+use alloy::{
+    network::EthereumWallet,
+    primitives::{Address, TxHash},
+    providers::{Provider, ProviderBuilder, WalletProvider, WsConnect},
+    transports::Transport,
+};
+use anyhow::Result;
+use axum::{
+    Router,
+    extract::{ConnectInfo, State as AxumState, WebSocketUpgrade},
+    http::HeaderMap,
+    response::IntoResponse,
+    routing::{get, post},
+};
+use backend::BackendContext;
+use candles::CandleAggregator;
+use chain::AlloyChain;
+use config::Config;
+use faucet::Faucet;
+use fees::FeeOracle;
+use nonce::NonceManager;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use store::Store;
+use supervisor::Supervisor;
+pub use supervisor::shutdown_signal;
+use tokio::{
+    net::TcpListener,
+    sync::{RwLock, broadcast, mpsc},
+};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tower_http::services::ServeDir;
+use ws::{PricePoint, ServerMessage};
+
+#[derive(Debug)]
+pub enum BackendTxEvent {
+    Fund(Address, IpAddr, mpsc::Sender<ServerMessage>),
+    Tick,
+    GameOver,
+}
+
+pub struct AppState {
+    pub names: HashMap<Address, String>,
+    pub seen_logs: HashSet<(TxHash, u64)>,
+    pub current_price: u64,
+    pub balances: HashMap<Address, u64>,
+    pub holdings: HashMap<Address, u64>,
+    pub last_position_block: u64,
+    pub game_start_block: Option<u64>,
+    pub game_end_block: Option<u64>,
+    /// reset() txs the backend sent for a restart that hasn't started yet.
+    /// Their Positions put everyone back to the initial position and aren't
+    /// trades, even when the restart interrupts a running game.
+    pub reset_txs: HashSet<TxHash>,
+    pub current_block_height: u64,
+    /// Most recent `PRICE_HISTORY_LEN` price updates, oldest first. The whole
+    /// game's are in the store.
+    pub price_history: VecDeque<PricePoint>,
+    pub candles: CandleAggregator,
+    pub store: Arc<Store>,
+}
+
+const PRICE_HISTORY_LEN: usize = 1000;
+
+impl AppState {
+    fn new(store: Arc<Store>, candle_windows: &[u64]) -> Result<Self> {
+        let names = store.load_names()?;
+        let seen_logs = store.load_seen_logs()?;
+        let (balances, holdings) = store.load_positions()?;
+        let prices = store.load_prices(0, u64::MAX)?;
+        let price_history = prices[prices.len().saturating_sub(PRICE_HISTORY_LEN)..]
+            .iter()
+            .map(|&(block_number, price)| PricePoint {
+                block_number,
+                price,
+            })
+            .collect();
+        let candles = CandleAggregator::new(candle_windows);
+        tracing::info!(
+            "Loaded persisted state: {} names, {} seen logs, {} positions",
+            names.len(),
+            seen_logs.len(),
+            balances.len()
+        );
+
+        Ok(Self {
+            names,
+            seen_logs,
+            current_price: 50,
+            balances,
+            holdings,
+            last_position_block: 0,
+            game_start_block: None,
+            game_end_block: None,
+            reset_txs: HashSet::new(),
+            current_block_height: 0,
+            price_history,
+            candles,
+            store,
+        })
+    }
+
+    pub fn push_price(&mut self, point: PricePoint) {
+        if self.price_history.len() == PRICE_HISTORY_LEN {
+            self.price_history.pop_front();
+        }
+        self.price_history.push_back(point);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GasCosts {
+    pub register: u64,
+    pub buy: u64,
+    pub sell: u64,
+}
+
+#[derive(Clone)]
+struct ServerState<T: Transport + Clone, P: Provider<T> + WalletProvider + Clone + 'static> {
+    app_state: Arc<RwLock<AppState>>,
+    broadcast_tx: broadcast::Sender<ServerMessage>,
+    provider: P,
+    gas_costs: Arc<RwLock<GasCosts>>,
+    contract_address: Address,
+    chain_id: u64,
+    backend_tx_sender: mpsc::Sender<BackendTxEvent>,
+    backend: BackendContext,
+    chain: AlloyChain<T, P>,
+    name_rules: Arc<names::NameRules>,
+    config: Arc<Config>,
+    /// Cancelled when the server shuts down.
+    shutdown: CancellationToken,
+    /// Websocket connections and work they started that should finish before
+    /// exit (e.g. a restart sequence).
+    tasks: TaskTracker,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+// How long queued backend txs and open connections get to finish on shutdown.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+
+/// Runs the server until `shutdown_signal` resolves or a critical task
+/// fails: connects to the node, spawns the backend tasks under a
+/// `Supervisor` and serves HTTP and `/ws` on `listener`. State is flushed to
+/// disk before returning either way.
+pub async fn run(
+    config: Arc<Config>,
+    listener: TcpListener,
+    shutdown_signal: impl Future<Output = ()> + Send + 'static,
+) -> Result<()> {
+    tracing::info!("Connecting to RPC: {}", config.rpc_url);
+    tracing::info!("Contract address: {:?}", config.contract_address);
+    tracing::info!("State database: {}", config.state_db.display());
+
+    let wallet = EthereumWallet::from(config.signer()?);
+
+    let ws_write = WsConnect::new(&config.rpc_url);
+    let provider_write = ProviderBuilder::new()
+        .with_recommended_fillers()
+        .wallet(wallet)
+        .on_ws(ws_write)
+        .await?;
+
+    let contract_addr = config.contract_address;
+    let chain_id = provider_write.get_chain_id().await?;
+    tracing::info!("Chain id: {}", chain_id);
+
+    let store = Arc::new(Store::open(&config.state_db, contract_addr, chain_id)?);
+    let state = Arc::new(RwLock::new(AppState::new(
+        store.clone(),
+        &config.candles.windows,
+    )?));
+
+    tracing::info!("Querying contract for game state...");
+    let contract = backend::StockMarket::new(contract_addr, &provider_write);
+    let start_block = contract.startBlock().call().await?._0;
+    let end_block = contract.endBlock().call().await?._0;
+    let price = contract.price().call().await?._0;
+    state.write().await.current_price = price.to();
+
+    if start_block > 0 {
+        tracing::info!(
+            "Game state: started at block {}, ends at block {}",
+            start_block,
+            end_block
+        );
+        let mut state_guard = state.write().await;
+        state_guard.game_start_block = Some(start_block.to());
+        state_guard.game_end_block = Some(end_block.to());
+    } else {
+        tracing::info!("Game not started yet");
+    }
+
+    tracing::info!("Calculating gas costs...");
+    let gas_costs = gas::estimate_gas_costs(
+        &provider_write,
+        contract_addr,
+        &state,
+        config.gas.estimate_margin_percent,
+        &gas::FALLBACK_GAS_COSTS,
+    )
+    .await;
+    tracing::info!("Gas costs calculated:");
+    tracing::info!("  register: {} gas", gas_costs.register);
+    tracing::info!("  buy: {} gas", gas_costs.buy);
+    tracing::info!("  sell: {} gas", gas_costs.sell);
+    let gas_costs = Arc::new(RwLock::new(gas_costs));
+
+    let nonces = Arc::new(NonceManager::new(&provider_write).await?);
+    tracing::info!(
+        "Backend wallet {:?} starting nonce: {}",
+        nonces.address(),
+        nonces.next_nonce().await
+    );
+    let fees = Arc::new(FeeOracle::new(config.clone()));
+    let chain = AlloyChain::new(
+        provider_write.clone(),
+        nonces.clone(),
+        fees.clone(),
+        config.clone(),
+    );
+    let backend = BackendContext {
+        faucet: Arc::new(Faucet::new(config.faucet.clone(), store.clone())),
+        config: config.clone(),
+    };
+
+    let (broadcast_tx, _) = broadcast::channel::<ServerMessage>(1000);
+    let (backend_tx_sender, backend_tx_receiver) = mpsc::channel::<BackendTxEvent>(100);
+
+    let shutdown = CancellationToken::new();
+    let mut supervisor = Supervisor::new(shutdown.clone());
+    {
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            shutdown_signal.await;
+            tracing::info!("🛑 Shutting down...");
+            shutdown.cancel();
+        });
+    }
+
+    supervisor.spawn_critical(
+        "backend tx executor",
+        backend::backend_tx_executor(
+            backend_tx_receiver,
+            chain.clone(),
+            broadcast_tx.clone(),
+            backend.clone(),
+            shutdown.clone(),
+        ),
+    );
+
+    let server_state = ServerState {
+        app_state: state.clone(),
+        broadcast_tx: broadcast_tx.clone(),
+        provider: provider_write.clone(),
+        gas_costs: gas_costs.clone(),
+        contract_address: contract_addr,
+        chain_id,
+        backend_tx_sender: backend_tx_sender.clone(),
+        backend: backend.clone(),
+        chain,
+        name_rules: Arc::new(names::NameRules::new(
+            config.names.clone(),
+            Box::new(names::Blocklist::new(&config.names.blocklist)),
+        )),
+        config: config.clone(),
+        shutdown: shutdown.clone(),
+        tasks: TaskTracker::new(),
+        _phantom: std::marker::PhantomData,
+    };
+    supervisor.spawn_critical("HTTP server", run_http_server(server_state, listener));
+
+    {
+        let provider = provider_write.clone();
+        let nonces = nonces.clone();
+        let fees = fees.clone();
+        supervisor.spawn_restarting("nonce maintenance", move || {
+            nonce::run_nonce_maintenance(provider.clone(), nonces.clone(), fees.clone())
+        });
+    }
+    {
+        let provider = provider_write.clone();
+        let config = config.clone();
+        supervisor.spawn_restarting("fee bumper", move || {
+            fees::run_fee_bumper(
+                provider.clone(),
+                nonces.clone(),
+                fees.clone(),
+                config.clone(),
+            )
+        });
+    }
+    {
+        let provider = provider_write.clone();
+        let state = state.clone();
+        let broadcast_tx = broadcast_tx.clone();
+        let config = config.clone();
+        supervisor.spawn_restarting("gas estimator", move || {
+            gas::run_gas_estimator(
+                provider.clone(),
+                contract_addr,
+                state.clone(),
+                gas_costs.clone(),
+                broadcast_tx.clone(),
+                config.clone(),
+            )
+        });
+    }
+    {
+        let state = state.clone();
+        let broadcast_tx = broadcast_tx.clone();
+        supervisor.spawn_restarting("leaderboard", move || {
+            leaderboard::run_leaderboard(state.clone(), broadcast_tx.clone())
+        });
+    }
+
+    let backfill_from_block = match config.backfill_from_block {
+        Some(block) => Some(block),
+        None => state.read().await.game_start_block,
+    };
+
+    let subscriber = subscription::ChainSubscriber::new(
+        config.rpc_url.clone(),
+        contract_addr,
+        state,
+        broadcast_tx,
+        backend_tx_sender,
+        shutdown,
+    );
+    supervisor.spawn_critical("chain subscriber", subscriber.run(backfill_from_block));
+
+    let result = supervisor.wait().await;
+    supervisor.drain(SHUTDOWN_GRACE).await;
+    match store.flush().await {
+        Ok(bytes) => tracing::info!("💾 State flushed ({} bytes)", bytes),
+        Err(e) => tracing::error!("Failed to flush state: {}", e),
+    }
+    result
+}
+
+async fn run_http_server<T, P>(server_state: ServerState<T, P>, listener: TcpListener) -> Result<()>
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    let static_dir = server_state.config.static_dir.clone();
+    let addr = listener.local_addr()?;
+    let shutdown = server_state.shutdown.clone();
+    let tasks = server_state.tasks.clone();
+
+    let app = Router::new()
+        .route("/ws", get(ws_handler))
+        .nest("/api", api::routes())
+        .route("/metrics", get(metrics::handler))
+        .route("/admin/session", post(admin::login))
+        .fallback_service(ServeDir::new(&static_dir))
+        .with_state(server_state);
+
+    tracing::info!("HTTP server listening on {}", addr);
+    tracing::info!("  WebSocket endpoint: ws://{}/ws", addr);
+    tracing::info!("  HTTP API: http://{}/api", addr);
+    tracing::info!("  Metrics: http://{}/metrics", addr);
+    tracing::info!("  Static files from: {}", static_dir.display());
+
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown.cancelled_owned())
+    .await?;
+
+    // Upgraded websockets outlive the server future; each sends its close
+    // frame and ends on its own.
+    tasks.close();
+    tracing::info!("Waiting for {} connections and tasks", tasks.len());
+    tasks.wait().await;
+
+    Ok(())
+}
+
+async fn ws_handler<T, P>(
+    ws: WebSocketUpgrade,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    AxumState(state): AxumState<ServerState<T, P>>,
+) -> impl IntoResponse
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    let client = ws_axum::ClientInfo {
+        peer,
+        ip: client_ip(&headers, peer, state.config.faucet.trusted_proxies),
+        is_admin: admin::is_admin(&headers, &state.config.admin),
+    };
+    if client.is_admin {
+        tracing::info!(target: "audit", "🔑 Admin connected from {}", peer);
+    }
+    let tasks = state.tasks.clone();
+    ws.on_upgrade(move |socket| {
+        tasks.track_future(async move {
+            let _connected = metrics::METRICS.ws_clients.track();
+            if let Err(e) = ws_axum::handle_axum_connection(socket, state, client).await {
+                tracing::error!("WebSocket connection error: {}", e);
+            }
+        })
+    })
+}
+
+/// The address limits are keyed on. Behind reverse proxies every peer is the
+/// nearest proxy, so X-Forwarded-For is read instead. Clients can put
+/// anything in the header before it reaches us; only the entries our own
+/// `trusted_proxies` appended, counted from the right, can be believed.
+fn client_ip(headers: &HeaderMap, peer: SocketAddr, trusted_proxies: usize) -> IpAddr {
+    if trusted_proxies == 0 {
+        return peer.ip();
+    }
+    let hops: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+    // Fewer hops than proxies: the request skipped one of them, the
+    // left-most entry is still one a proxy wrote.
+    hops.iter()
+        .rev()
+        .nth(trusted_proxies - 1)
+        .or(hops.first())
+        .and_then(|ip| ip.parse().ok())
+        .unwrap_or(peer.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn forwarded_for(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn client_ip_ignores_spoofed_forwarded_for_entries() {
+        let peer: SocketAddr = "10.0.0.1:443".parse().unwrap();
+        let spoofed = forwarded_for("1.2.3.4, 203.0.113.7");
+
+        assert_eq!(client_ip(&spoofed, peer, 0), peer.ip());
+        assert_eq!(
+            client_ip(&spoofed, peer, 1),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+
+        // CDN in front of the load balancer: skip the CDN's own entry.
+        let two_proxies = forwarded_for("1.2.3.4, 203.0.113.7, 198.51.100.2");
+        assert_eq!(
+            client_ip(&two_proxies, peer, 2),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(client_ip(&HeaderMap::new(), peer, 1), peer.ip());
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{ServerState, chain::MarketChain};
+use alloy::{
+    providers::{Provider, WalletProvider},
+    transports::Transport,
+};
+use axum::{extract::State as AxumState, http::header, response::IntoResponse};
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Process-wide metrics, rendered in the Prometheus text format by
+/// `/metrics`. Values that can be read off existing state (price, lags,
+/// wallet balance) are sampled at scrape time instead of being kept here.
+pub static METRICS: Metrics = Metrics {
+    ticks_sent: Counter::new(),
+    ticks_failed: Counter::new(),
+    funds_sent: Counter::new(),
+    funds_failed: Counter::new(),
+    raw_txs_relayed: Counter::new(),
+    raw_txs_rejected: Counter::new(),
+    nonce_resyncs: Counter::new(),
+    nonce_jumps: Counter::new(),
+    ws_clients: Gauge::new(),
+    receipt_latency: Histogram::new(),
+};
+
+pub struct Metrics {
+    pub ticks_sent: Counter,
+    pub ticks_failed: Counter,
+    pub funds_sent: Counter,
+    pub funds_failed: Counter,
+    pub raw_txs_relayed: Counter,
+    pub raw_txs_rejected: Counter,
+    /// Resyncs that changed anything: forgot mined txs or moved the nonce
+    pub nonce_resyncs: Counter,
+    /// Resyncs that moved the local nonce forward
+    pub nonce_jumps: Counter,
+    pub ws_clients: Gauge,
+    /// From starting to wait on a backend tx to its receipt
+    pub receipt_latency: Histogram,
+}
+
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+pub struct Gauge(AtomicI64);
+
+impl Gauge {
+    const fn new() -> Self {
+        Self(AtomicI64::new(0))
+    }
+
+    /// Counts one up until the guard is dropped, however the holder ends.
+    pub fn track(&'static self) -> GaugeGuard {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        GaugeGuard(self)
+    }
+
+    fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+pub struct GaugeGuard(&'static Gauge);
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.0.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Upper bounds in seconds; a block is ~0.5s on Monad.
+const LATENCY_BUCKETS: [f64; 9] = [0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0, 300.0];
+
+pub struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; LATENCY_BUCKETS.len()],
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(
+        out,
+        "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}"
+    );
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(
+        out,
+        "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}"
+    );
+}
+
+fn histogram(out: &mut String, name: &str, help: &str, histogram: &Histogram) {
+    let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} histogram");
+    for (bucket, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{le=\"{bound}\"}} {}",
+            bucket.load(Ordering::Relaxed)
+        );
+    }
+    let count = histogram.count.load(Ordering::Relaxed);
+    let sum = histogram.sum_micros.load(Ordering::Relaxed) as f64 / 1e6;
+    let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}");
+    let _ = writeln!(out, "{name}_sum {sum}\n{name}_count {count}");
+}
+
+pub(crate) async fn handler<T, P>(
+    AxumState(state): AxumState<ServerState<T, P>>,
+) -> impl IntoResponse
+where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider + Clone + 'static,
+{
+    let m = &METRICS;
+    let mut out = String::new();
+
+    counter(
+        &mut out,
+        "monomarket_ticks_sent_total",
+        "Tick transactions accepted by the node",
+        m.ticks_sent.get(),
+    );
+    counter(
+        &mut out,
+        "monomarket_ticks_failed_total",
+        "Tick transactions that could not be sent",
+        m.ticks_failed.get(),
+    );
+    counter(
+        &mut out,
+        "monomarket_funds_sent_total",
+        "Funding transfers accepted by the node",
+        m.funds_sent.get(),
+    );
+    counter(
+        &mut out,
+        "monomarket_funds_failed_total",
+        "Funding transfers that could not be sent or reverted",
+        m.funds_failed.get(),
+    );
+    counter(
+        &mut out,
+        "monomarket_raw_txs_relayed_total",
+        "Client transactions relayed to the node",
+        m.raw_txs_relayed.get(),
+    );
+    counter(
+        &mut out,
+        "monomarket_raw_txs_rejected_total",
+        "Client transactions rejected by preflight or the node",
+        m.raw_txs_rejected.get(),
+    );
+    counter(
+        &mut out,
+        "monomarket_nonce_resyncs_total",
+        "Backend nonce resyncs that forgot mined txs or moved the nonce",
+        m.nonce_resyncs.get(),
+    );
+    counter(
+        &mut out,
+        "monomarket_nonce_jumps_total",
+        "Resyncs that moved the backend nonce forward",
+        m.nonce_jumps.get(),
+    );
+
+    gauge(
+        &mut out,
+        "monomarket_ws_clients",
+        "Connected websocket clients",
+        m.ws_clients.get(),
+    );
+    let (price, height) = {
+        let state_guard = state.app_state.read().await;
+        (state_guard.current_price, state_guard.current_block_height)
+    };
+    gauge(&mut out, "monomarket_price", "Current stock price", price);
+    gauge(
+        &mut out,
+        "monomarket_broadcast_lag",
+        "Messages queued for the slowest broadcast subscriber",
+        state.broadcast_tx.len(),
+    );
+    match state.provider.get_block_number().await {
+        Ok(head) => gauge(
+            &mut out,
+            "monomarket_block_lag",
+            "Blocks between the node's head and the last block processed",
+            head.saturating_sub(height),
+        ),
+        Err(e) => tracing::warn!("⚠️  Failed to read block number for metrics: {}", e),
+    }
+    match state.chain.get_balance(state.chain.backend_address()).await {
+        Ok(balance) => gauge(
+            &mut out,
+            "monomarket_backend_wallet_balance_wei",
+            "Native balance of the backend wallet",
+            balance,
+        ),
+        Err(e) => tracing::warn!("⚠️  Failed to read wallet balance for metrics: {}", e),
+    }
+
+    histogram(
+        &mut out,
+        "monomarket_receipt_latency_seconds",
+        "Time from sending a backend transaction to its receipt",
+        &m.receipt_latency,
+    );
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+}
@@ -0,0 +1,176 @@
+use crate::{config::NameConfig, ws::NameErrorReason};
+use alloy::primitives::Address;
+use std::collections::HashMap;
+
+#[derive(Debug)]
+pub struct NameRejection {
+    pub reason: NameErrorReason,
+    pub message: String,
+}
+
+impl NameRejection {
+    pub fn new(reason: NameErrorReason, message: impl Into<String>) -> Self {
+        Self {
+            reason,
+            message: message.into(),
+        }
+    }
+}
+
+/// Hook for deciding whether a display name is acceptable beyond the length
+/// and charset rules, e.g. a profanity filter.
+pub trait NameFilter: Send + Sync {
+    fn allows(&self, name: &str) -> bool;
+}
+
+/// Rejects names containing any of the configured words, ignoring case.
+pub struct Blocklist(Vec<String>);
+
+impl Blocklist {
+    pub fn new(words: &[String]) -> Self {
+        Self(words.iter().map(|word| word.to_lowercase()).collect())
+    }
+}
+
+impl NameFilter for Blocklist {
+    fn allows(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        !self.0.iter().any(|word| name.contains(word.as_str()))
+    }
+}
+
+pub struct NameRules {
+    config: NameConfig,
+    filter: Box<dyn NameFilter>,
+}
+
+impl NameRules {
+    /// `filter` runs after the length and charset checks.
+    pub fn new(config: NameConfig, filter: Box<dyn NameFilter>) -> Self {
+        Self { config, filter }
+    }
+
+    /// Validates `name` for `address` against the names already taken and
+    /// returns it trimmed. Names are unique ignoring case; an address may
+    /// re-register its own name.
+    pub fn check(
+        &self,
+        address: Address,
+        name: &str,
+        taken: &HashMap<Address, String>,
+    ) -> Result<String, NameRejection> {
+        let name = name.trim();
+        let len = name.chars().count();
+        if len < self.config.min_len || len > self.config.max_len {
+            return Err(NameRejection::new(
+                NameErrorReason::InvalidLength,
+                format!(
+                    "Name must be {} to {} characters",
+                    self.config.min_len, self.config.max_len
+                ),
+            ));
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '_' | '-' | '.'))
+        {
+            return Err(NameRejection::new(
+                NameErrorReason::InvalidCharacters,
+                "Name may only contain letters, digits, spaces, '_', '-' and '.'",
+            ));
+        }
+        if !self.filter.allows(name) {
+            return Err(NameRejection::new(
+                NameErrorReason::Rejected,
+                "Name is not allowed",
+            ));
+        }
+        if taken
+            .iter()
+            .any(|(other, existing)| *other != address && existing.eq_ignore_ascii_case(name))
+        {
+            return Err(NameRejection::new(
+                NameErrorReason::Taken,
+                format!("'{}' is already taken", name),
+            ));
+        }
+        Ok(name.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: Address = Address::repeat_byte(0xa1);
+    const BOB: Address = Address::repeat_byte(0xb0);
+
+    fn rules(blocklist: &[&str]) -> NameRules {
+        let config = NameConfig {
+            min_len: 3,
+            max_len: 5,
+            blocklist: blocklist.iter().map(|word| word.to_string()).collect(),
+        };
+        let filter = Box::new(Blocklist::new(&config.blocklist));
+        NameRules::new(config, filter)
+    }
+
+    fn reason(result: Result<String, NameRejection>) -> NameErrorReason {
+        result.unwrap_err().reason
+    }
+
+    #[test]
+    fn length_is_counted_in_chars_after_trimming() {
+        let rules = rules(&[]);
+        let taken = HashMap::new();
+        assert_eq!(rules.check(ALICE, "  abc  ", &taken).unwrap(), "abc");
+        assert_eq!(rules.check(ALICE, "abcde", &taken).unwrap(), "abcde");
+        assert_eq!(
+            reason(rules.check(ALICE, " ab ", &taken)),
+            NameErrorReason::InvalidLength
+        );
+        assert_eq!(
+            reason(rules.check(ALICE, "abcdef", &taken)),
+            NameErrorReason::InvalidLength
+        );
+        // 4 characters but 8 bytes: within bounds, refused for the charset.
+        assert_eq!(
+            reason(rules.check(ALICE, "éééé", &taken)),
+            NameErrorReason::InvalidCharacters
+        );
+    }
+
+    #[test]
+    fn charset_is_ascii_alphanumeric_and_a_few_separators() {
+        let rules = rules(&[]);
+        let taken = HashMap::new();
+        assert!(rules.check(ALICE, "a b_c", &taken).is_ok());
+        assert!(rules.check(ALICE, "a-b.c", &taken).is_ok());
+        assert_eq!(
+            reason(rules.check(ALICE, "a<b>", &taken)),
+            NameErrorReason::InvalidCharacters
+        );
+    }
+
+    #[test]
+    fn names_are_unique_ignoring_case() {
+        let rules = rules(&[]);
+        let taken = HashMap::from([(ALICE, "Alice".to_string())]);
+        assert_eq!(
+            reason(rules.check(BOB, "aLICE", &taken)),
+            NameErrorReason::Taken
+        );
+        assert_eq!(rules.check(ALICE, "ALICE", &taken).unwrap(), "ALICE");
+    }
+
+    #[test]
+    fn blocklist_matches_substrings_ignoring_case() {
+        let rules = rules(&["Bad"]);
+        let taken = HashMap::new();
+        assert_eq!(
+            reason(rules.check(ALICE, "xBADx", &taken)),
+            NameErrorReason::Rejected
+        );
+        assert!(rules.check(ALICE, "b.a.d", &taken).is_ok());
+    }
+}
@@ -0,0 +1,403 @@
+use crate::{
+    fees::{FeeOracle, Fees, TxClass},
+    metrics::METRICS,
+};
+use alloy::{
+    network::TransactionBuilder,
+    primitives::{Address, TxHash, U256},
+    providers::{Provider, WalletProvider},
+    rpc::types::{TransactionReceipt, TransactionRequest},
+    transports::Transport,
+};
+use anyhow::{Result, bail};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+const NOOP_GAS_LIMIT: u64 = 21_000;
+// Stale txs are re-broadcast after a few seconds, so a receipt that hasn't
+// shown up by now isn't coming.
+const RECEIPT_TIMEOUT: Duration = Duration::from_secs(120);
+const RECEIPT_POLL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone)]
+pub struct PendingTx {
+    pub tx_hash: TxHash,
+    pub request: TransactionRequest,
+    pub sent_at: Instant,
+    pub class: TxClass,
+    /// Hashes this nonce was broadcast under before being replaced; any of
+    /// them may be the one that gets mined.
+    pub replaced: Vec<TxHash>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    next: u64,
+    pending: BTreeMap<u64, PendingTx>,
+}
+
+/// Owns the backend wallet's nonce sequence. Every backend transaction goes
+/// through `send`, which assigns the next nonce and remembers the tx until the
+/// chain's `latest` count moves past it.
+pub struct NonceManager {
+    address: Address,
+    inner: Mutex<Inner>,
+}
+
+impl NonceManager {
+    pub async fn new<T, P>(provider: &P) -> Result<Self>
+    where
+        T: Transport + Clone,
+        P: Provider<T> + WalletProvider,
+    {
+        let manager = Self {
+            address: provider.default_signer_address(),
+            inner: Mutex::new(Inner::default()),
+        };
+        manager.resync(provider).await?;
+        Ok(manager)
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    pub async fn next_nonce(&self) -> u64 {
+        self.inner.lock().await.next
+    }
+
+    /// Assigns the next nonce to `tx` and broadcasts it. The lock is held for
+    /// the whole send so transactions reach the node in nonce order; if the
+    /// node rejects the tx the nonce is handed back.
+    pub async fn send<T, P>(
+        &self,
+        provider: &P,
+        tx: TransactionRequest,
+        class: TxClass,
+    ) -> Result<(u64, TxHash)>
+    where
+        T: Transport + Clone,
+        P: Provider<T> + WalletProvider,
+    {
+        let mut inner = self.inner.lock().await;
+        let nonce = inner.next;
+        let tx = tx.with_nonce(nonce);
+
+        let pending = provider.send_transaction(tx.clone()).await?;
+        let tx_hash = *pending.tx_hash();
+        inner.next += 1;
+        inner.pending.insert(
+            nonce,
+            PendingTx {
+                tx_hash,
+                request: tx,
+                sent_at: Instant::now(),
+                class,
+                replaced: Vec::new(),
+            },
+        );
+        Ok((nonce, tx_hash))
+    }
+
+    /// Re-broadcasts `tx` at a nonce that is already in flight, e.g. with
+    /// bumped fees. The tracked entry is updated to the new hash; a nonce we
+    /// weren't tracking is taken as `Urgent`.
+    pub async fn replace<T, P>(
+        &self,
+        provider: &P,
+        nonce: u64,
+        tx: TransactionRequest,
+    ) -> Result<TxHash>
+    where
+        T: Transport + Clone,
+        P: Provider<T> + WalletProvider,
+    {
+        let mut inner = self.inner.lock().await;
+        let tx = tx.with_nonce(nonce);
+        let pending = provider.send_transaction(tx.clone()).await?;
+        let tx_hash = *pending.tx_hash();
+        let mut replaced = Vec::new();
+        let mut class = TxClass::Urgent;
+        if let Some(previous) = inner.pending.remove(&nonce) {
+            class = previous.class;
+            replaced = previous.replaced;
+            if previous.tx_hash != tx_hash {
+                replaced.push(previous.tx_hash);
+            }
+        }
+        inner.pending.insert(
+            nonce,
+            PendingTx {
+                tx_hash,
+                request: tx,
+                sent_at: Instant::now(),
+                class,
+                replaced,
+            },
+        );
+        Ok(tx_hash)
+    }
+
+    /// Txs still in flight that were last broadcast more than `older_than` ago.
+    pub async fn stale(&self, older_than: Duration) -> Vec<(u64, PendingTx)> {
+        let inner = self.inner.lock().await;
+        inner
+            .pending
+            .iter()
+            .filter(|(_, tx)| tx.sent_at.elapsed() > older_than)
+            .map(|(nonce, tx)| (*nonce, tx.clone()))
+            .collect()
+    }
+
+    /// Polls until the tx at `nonce` is mined. Follows replacements, so this
+    /// still resolves if the tx was fee-bumped or re-broadcast meanwhile.
+    /// Fails once the nonce is mined under a hash we never sent, or after
+    /// `RECEIPT_TIMEOUT`.
+    pub async fn wait_for_receipt<T, P>(
+        &self,
+        provider: &P,
+        nonce: u64,
+        tx_hash: TxHash,
+    ) -> Result<TransactionReceipt>
+    where
+        T: Transport + Clone,
+        P: Provider<T> + WalletProvider,
+    {
+        let started = Instant::now();
+        let mut hashes = vec![tx_hash];
+        loop {
+            // Read the count first: if it already covers the nonce and none of
+            // the hashes sent for it up to now has a receipt, someone else's
+            // tx took it.
+            let mined = provider
+                .get_transaction_count(self.address)
+                .latest()
+                .await?;
+            if let Some(pending) = self.inner.lock().await.pending.get(&nonce) {
+                for hash in pending.replaced.iter().chain([&pending.tx_hash]) {
+                    if !hashes.contains(hash) {
+                        hashes.push(*hash);
+                    }
+                }
+            }
+            for hash in &hashes {
+                if let Some(receipt) = provider.get_transaction_receipt(*hash).await? {
+                    METRICS.receipt_latency.observe(started.elapsed());
+                    return Ok(receipt);
+                }
+            }
+            if mined > nonce {
+                bail!("nonce {} was mined by a different transaction", nonce);
+            }
+            if started.elapsed() > RECEIPT_TIMEOUT {
+                bail!("no receipt for nonce {} after {:?}", nonce, RECEIPT_TIMEOUT);
+            }
+            tokio::time::sleep(RECEIPT_POLL).await;
+        }
+    }
+
+    /// Reconciles local state with the node. Everything below the `latest`
+    /// count is mined and forgotten; if the `pending` count is ahead of us
+    /// (someone else used the key, or we lost track after an error) we skip
+    /// forward to it instead of guessing.
+    pub async fn resync<T, P>(&self, provider: &P) -> Result<()>
+    where
+        T: Transport + Clone,
+        P: Provider<T> + WalletProvider,
+    {
+        let latest = provider
+            .get_transaction_count(self.address)
+            .latest()
+            .await?;
+        let pending = provider
+            .get_transaction_count(self.address)
+            .pending()
+            .await?;
+
+        let mut inner = self.inner.lock().await;
+        let tracked = inner.pending.len();
+        inner.pending = inner.pending.split_off(&latest);
+        let dropped = inner.pending.len() < tracked;
+
+        let old_next = inner.next;
+        let floor = latest.max(pending);
+        let jumped = floor > inner.next;
+        if dropped || jumped {
+            METRICS.nonce_resyncs.inc();
+        }
+        if jumped {
+            inner.next = floor;
+            METRICS.nonce_jumps.inc();
+            tracing::info!(
+                "✅ Nonce resynced upward: {} -> {} (latest: {}, pending: {})",
+                old_next,
+                floor,
+                latest,
+                pending
+            );
+        }
+        Ok(())
+    }
+
+    /// Nonces between the mined count and our next nonce that the node does
+    /// not know about. Any tx queued above a gap is stuck until it is filled.
+    pub async fn gaps<T, P>(&self, provider: &P) -> Result<Vec<u64>>
+    where
+        T: Transport + Clone,
+        P: Provider<T> + WalletProvider,
+    {
+        let latest = provider
+            .get_transaction_count(self.address)
+            .latest()
+            .await?;
+        let pending = provider
+            .get_transaction_count(self.address)
+            .pending()
+            .await?;
+        let (next, tracked) = {
+            let inner = self.inner.lock().await;
+            (inner.next, inner.pending.clone())
+        };
+
+        let mut dropped = HashSet::new();
+        for (&nonce, tx) in tracked.range(latest..next) {
+            if provider
+                .get_transaction_by_hash(tx.tx_hash)
+                .await?
+                .is_none()
+            {
+                dropped.insert(nonce);
+            }
+        }
+        Ok(find_gaps(latest, pending, next, &tracked, &dropped))
+    }
+
+    /// Fills every gap: a dropped tx we still know about is re-broadcast as-is,
+    /// otherwise a zero-value self transfer is sent to burn the nonce.
+    pub async fn fill_gaps<T, P>(&self, provider: &P, fees: Fees) -> Result<usize>
+    where
+        T: Transport + Clone,
+        P: Provider<T> + WalletProvider,
+    {
+        let gaps = self.gaps(provider).await?;
+        for &nonce in &gaps {
+            let known = self.inner.lock().await.pending.get(&nonce).cloned();
+            let (tx, kind) = match known {
+                Some(pending) => (pending.request, "replacement"),
+                None => (
+                    fees.apply(
+                        TransactionRequest::default()
+                            .to(self.address)
+                            .value(U256::ZERO)
+                            .with_gas_limit(NOOP_GAS_LIMIT),
+                    ),
+                    "no-op",
+                ),
+            };
+            match self.replace(provider, nonce, tx).await {
+                Ok(tx_hash) => {
+                    tracing::info!(
+                        "🩹 Filled nonce gap {} with {} tx {:?}",
+                        nonce,
+                        kind,
+                        tx_hash
+                    )
+                }
+                Err(e) => tracing::error!("Failed to fill nonce gap {}: {}", nonce, e),
+            }
+        }
+        Ok(gaps.len())
+    }
+}
+
+/// Below the node's `pending` count every nonce is held by some tx, possibly
+/// one we lost track of (sent before a restart, or broadcast despite a send
+/// error), so only our own dropped txs count as gaps there. Burning those
+/// nonces would cancel real transactions.
+fn find_gaps(
+    latest: u64,
+    pending: u64,
+    next: u64,
+    tracked: &BTreeMap<u64, PendingTx>,
+    dropped: &HashSet<u64>,
+) -> Vec<u64> {
+    (latest..next)
+        .filter(|nonce| {
+            if tracked.contains_key(nonce) {
+                dropped.contains(nonce)
+            } else {
+                *nonce >= pending
+            }
+        })
+        .collect()
+}
+
+pub async fn run_nonce_maintenance<T, P>(
+    provider: P,
+    nonces: Arc<NonceManager>,
+    oracle: Arc<FeeOracle>,
+) where
+    T: Transport + Clone,
+    P: Provider<T> + WalletProvider,
+{
+    let mut interval = tokio::time::interval(Duration::from_secs(10));
+    loop {
+        interval.tick().await;
+        if let Err(e) = nonces.resync(&provider).await {
+            tracing::error!("Nonce resync failed: {}", e);
+            continue;
+        }
+        let fees = oracle.fees(&provider, TxClass::Urgent).await;
+        match nonces.fill_gaps(&provider, fees).await {
+            Ok(0) => {}
+            Ok(n) => tracing::warn!("⚠️  Filled {} nonce gaps", n),
+            Err(e) => tracing::error!("Nonce gap detection failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracked(nonces: &[u64]) -> BTreeMap<u64, PendingTx> {
+        nonces
+            .iter()
+            .map(|&nonce| {
+                let tx = PendingTx {
+                    tx_hash: TxHash::repeat_byte(nonce as u8),
+                    request: TransactionRequest::default(),
+                    sent_at: Instant::now(),
+                    class: TxClass::Urgent,
+                    replaced: Vec::new(),
+                };
+                (nonce, tx)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn restart_with_pending_txs_has_no_gaps() {
+        // The previous process left nonces 5..8 in the mempool; after resync
+        // `next` is 8 and none of them are tracked.
+        let gaps = find_gaps(5, 8, 8, &BTreeMap::new(), &HashSet::new());
+        assert!(gaps.is_empty());
+    }
+
+    #[test]
+    fn gaps_are_dropped_own_txs_or_above_pending() {
+        // 5..8 pending from before a restart, we sent 8 (dropped) and 9, and
+        // lost track of 10 after a send error.
+        let tracked = tracked(&[8, 9]);
+        let gaps = find_gaps(5, 8, 11, &tracked, &HashSet::from([8]));
+        assert_eq!(gaps, vec![8, 10]);
+
+        // A tx of ours the node still holds is never a gap.
+        let gaps = find_gaps(5, 10, 10, &tracked, &HashSet::new());
+        assert!(gaps.is_empty());
+    }
+}
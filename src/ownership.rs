@@ -0,0 +1,34 @@
+use alloy::primitives::{Address, PrimitiveSignature};
+use anyhow::{Result, bail};
+
+/// Text a player signs (EIP-191 `personal_sign`) to prove they own the
+/// address they ask to be funded. `session` is the random value sent in
+/// `ConnectionInfo`, so a signature can't be replayed on another connection.
+pub fn funding_message(address: Address, session: &str) -> String {
+    format!(
+        "Monomarket: request funds for {}\nSession: {}",
+        address.to_checksum(None),
+        session
+    )
+}
+
+/// Text signed to claim `name` for `address`, bound to the connection's
+/// session like `funding_message`.
+pub fn name_message(address: Address, name: &str, session: &str) -> String {
+    format!(
+        "Monomarket: set name for {} to \"{}\"\nSession: {}",
+        address.to_checksum(None),
+        name,
+        session
+    )
+}
+
+/// Checks that `signature` over `message` was produced by `address`.
+pub fn verify(address: Address, message: &str, signature: &str) -> Result<()> {
+    let signature: PrimitiveSignature = signature.parse()?;
+    let signer = signature.recover_address_from_msg(message)?;
+    if signer != address {
+        bail!("signed by {:?}, not {:?}", signer, address);
+    }
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use crate::{backend::StockMarket, ws::TxErrorReason};
+use alloy::{
+    consensus::{Transaction, TxEnvelope},
+    eips::eip2718::Decodable2718,
+    primitives::{Address, U256},
+    providers::Provider,
+    sol_types::SolCall,
+    transports::Transport,
+};
+
+/// Contract calls players are allowed to relay through the server.
+const ALLOWED_SELECTORS: &[([u8; 4], &str)] = &[
+    (StockMarket::registerCall::SELECTOR, "register"),
+    (StockMarket::buyCall::SELECTOR, "buy"),
+    (StockMarket::sellCall::SELECTOR, "sell"),
+];
+
+#[derive(Debug)]
+pub struct Rejection {
+    pub reason: TxErrorReason,
+    pub message: String,
+}
+
+impl Rejection {
+    fn new(reason: TxErrorReason, message: impl Into<String>) -> Self {
+        Self {
+            reason,
+            message: message.into(),
+        }
+    }
+}
+
+/// A raw tx that passed every check and can be relayed as-is.
+#[derive(Debug)]
+pub struct CheckedTx {
+    pub signer: Address,
+    pub method: &'static str,
+}
+
+/// Decodes a signed raw tx and makes sure it is a `register`/`buy`/`sell` call
+/// to our contract on our chain that the signer can pay for, so the server
+/// doesn't relay arbitrary transactions.
+pub async fn check_raw_tx<T, P>(
+    provider: &P,
+    raw: &[u8],
+    contract_address: Address,
+    chain_id: u64,
+) -> Result<CheckedTx, Rejection>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let (tx, max_cost) = decode(raw, contract_address, chain_id)?;
+    let balance = provider.get_balance(tx.signer).await.map_err(|e| {
+        Rejection::new(
+            TxErrorReason::Rpc,
+            format!("Failed to get balance of {:?}: {}", tx.signer, e),
+        )
+    })?;
+    check_balance(&tx, max_cost, balance)?;
+    Ok(tx)
+}
+
+/// The checks that don't need the node. Returns the tx along with the most
+/// it can cost its signer.
+fn decode(
+    raw: &[u8],
+    contract_address: Address,
+    chain_id: u64,
+) -> Result<(CheckedTx, U256), Rejection> {
+    let envelope = TxEnvelope::decode_2718(&mut &raw[..]).map_err(|e| {
+        Rejection::new(
+            TxErrorReason::Malformed,
+            format!("Failed to decode transaction: {}", e),
+        )
+    })?;
+
+    match envelope.chain_id() {
+        Some(id) if id == chain_id => {}
+        Some(id) => {
+            return Err(Rejection::new(
+                TxErrorReason::WrongChainId,
+                format!("Transaction is for chain {}, expected {}", id, chain_id),
+            ));
+        }
+        None => {
+            return Err(Rejection::new(
+                TxErrorReason::WrongChainId,
+                "Transaction has no chain id (pre-EIP-155)",
+            ));
+        }
+    }
+
+    if envelope.to() != Some(contract_address) {
+        return Err(Rejection::new(
+            TxErrorReason::WrongRecipient,
+            format!(
+                "Transaction must be sent to {:?}, got {:?}",
+                contract_address,
+                envelope.to()
+            ),
+        ));
+    }
+
+    let input = envelope.input();
+    let method = input
+        .get(..4)
+        .and_then(|selector| {
+            ALLOWED_SELECTORS
+                .iter()
+                .find(|(allowed, _)| allowed[..] == *selector)
+        })
+        .map(|(_, name)| *name)
+        .ok_or_else(|| {
+            Rejection::new(
+                TxErrorReason::UnknownSelector,
+                "Only register, buy and sell calls are accepted",
+            )
+        })?;
+
+    let signer = envelope.recover_signer().map_err(|e| {
+        Rejection::new(
+            TxErrorReason::InvalidSignature,
+            format!("Failed to recover signer: {}", e),
+        )
+    })?;
+
+    let max_cost = U256::from(envelope.gas_limit()) * U256::from(envelope.max_fee_per_gas())
+        + envelope.value();
+    Ok((CheckedTx { signer, method }, max_cost))
+}
+
+fn check_balance(tx: &CheckedTx, max_cost: U256, balance: U256) -> Result<(), Rejection> {
+    if balance < max_cost {
+        return Err(Rejection::new(
+            TxErrorReason::InsufficientBalance,
+            format!(
+                "{:?} has {} wei, {} needs up to {} wei",
+                tx.signer, balance, tx.method, max_cost
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        consensus::{SignableTransaction, TxEip1559},
+        eips::eip2718::Encodable2718,
+        network::TxSignerSync,
+        primitives::{Bytes, TxKind},
+        signers::local::PrivateKeySigner,
+    };
+
+    const CHAIN_ID: u64 = 10143;
+    const CONTRACT: Address = Address::repeat_byte(0xcc);
+
+    fn raw_tx(chain_id: u64, to: Address, input: Vec<u8>) -> (Vec<u8>, Address) {
+        let signer = PrivateKeySigner::random();
+        let mut tx = TxEip1559 {
+            chain_id,
+            nonce: 0,
+            gas_limit: 100_000,
+            max_fee_per_gas: 50_000_000_000,
+            max_priority_fee_per_gas: 1_000_000_000,
+            to: TxKind::Call(to),
+            value: U256::ZERO,
+            access_list: Default::default(),
+            input: Bytes::from(input),
+        };
+        let signature = signer.sign_transaction_sync(&mut tx).unwrap();
+        let envelope = TxEnvelope::from(tx.into_signed(signature));
+        (envelope.encoded_2718(), signer.address())
+    }
+
+    fn buy() -> Vec<u8> {
+        StockMarket::buyCall {
+            amount: U256::from(3),
+        }
+        .abi_encode()
+    }
+
+    fn reason<T: std::fmt::Debug>(result: Result<T, Rejection>) -> TxErrorReason {
+        result.unwrap_err().reason
+    }
+
+    #[test]
+    fn accepts_an_allowed_call_to_the_contract() {
+        let (raw, signer) = raw_tx(CHAIN_ID, CONTRACT, buy());
+        let (tx, max_cost) = decode(&raw, CONTRACT, CHAIN_ID).unwrap();
+        assert_eq!((tx.signer, tx.method), (signer, "buy"));
+        assert_eq!(max_cost, U256::from(100_000u64 * 50_000_000_000));
+    }
+
+    #[test]
+    fn rejects_another_chain() {
+        let (raw, _) = raw_tx(1, CONTRACT, buy());
+        assert_eq!(
+            reason(decode(&raw, CONTRACT, CHAIN_ID)),
+            TxErrorReason::WrongChainId
+        );
+    }
+
+    #[test]
+    fn rejects_another_recipient() {
+        let (raw, _) = raw_tx(CHAIN_ID, Address::repeat_byte(0xdd), buy());
+        assert_eq!(
+            reason(decode(&raw, CONTRACT, CHAIN_ID)),
+            TxErrorReason::WrongRecipient
+        );
+    }
+
+    #[test]
+    fn rejects_other_calls() {
+        let start = StockMarket::startCall {
+            length: U256::from(100),
+        }
+        .abi_encode();
+        for input in [start, Vec::new(), vec![0xd0]] {
+            let (raw, _) = raw_tx(CHAIN_ID, CONTRACT, input);
+            assert_eq!(
+                reason(decode(&raw, CONTRACT, CHAIN_ID)),
+                TxErrorReason::UnknownSelector
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_signers_who_cannot_pay_the_max_cost() {
+        let (raw, _) = raw_tx(CHAIN_ID, CONTRACT, buy());
+        let (tx, max_cost) = decode(&raw, CONTRACT, CHAIN_ID).unwrap();
+        assert!(check_balance(&tx, max_cost, max_cost).is_ok());
+        assert_eq!(
+            reason(check_balance(&tx, max_cost, max_cost - U256::from(1))),
+            TxErrorReason::InsufficientBalance
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use alloy::primitives::{Address, TxHash, U256};
+use anyhow::{Result, bail};
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// On-disk copy of the parts of `AppState` that can't be cheaply rebuilt from
+/// the chain. Each map gets its own sled tree keyed by raw address/hash bytes.
+pub struct Store {
+    db: sled::Db,
+    meta: sled::Tree,
+    names: sled::Tree,
+    seen_logs: sled::Tree,
+    positions: sled::Tree,
+    audit: sled::Tree,
+    funded: sled::Tree,
+    prices: sled::Tree,
+    trades: sled::Tree,
+    games: sled::Tree,
+    candles: sled::Tree,
+}
+
+impl Store {
+    /// Opens the store for the given deployment. The first open records the
+    /// contract address and chain id; opening the same directory for another
+    /// deployment fails instead of mixing its state in.
+    pub fn open(path: impl AsRef<Path>, contract: Address, chain_id: u64) -> Result<Self> {
+        let path = path.as_ref();
+        let store = Self::from_db(sled::open(path)?)?;
+        if let Err(e) = store.check_deployment(contract, chain_id) {
+            bail!(
+                "{}: {}; point state_db at a fresh directory or delete it",
+                path.display(),
+                e
+            );
+        }
+        Ok(store)
+    }
+
+    fn check_deployment(&self, contract: Address, chain_id: u64) -> Result<()> {
+        let stored_contract = self.meta.get("contract_address")?;
+        let stored_chain_id = self.meta.get("chain_id")?;
+        match (stored_contract, stored_chain_id) {
+            (Some(stored_contract), Some(stored_chain_id)) => {
+                let stored_contract = Address::from_slice(&stored_contract);
+                let stored_chain_id = u64::from_be_bytes(stored_chain_id[..].try_into()?);
+                if (stored_contract, stored_chain_id) != (contract, chain_id) {
+                    bail!(
+                        "state belongs to contract {:?} on chain {}, not {:?} on chain {}",
+                        stored_contract,
+                        stored_chain_id,
+                        contract,
+                        chain_id
+                    );
+                }
+            }
+            _ => {
+                self.meta.insert("contract_address", contract.as_slice())?;
+                self.meta.insert("chain_id", &chain_id.to_be_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes everything buffered to disk; sled otherwise only flushes
+    /// periodically. Returns the number of bytes flushed.
+    pub async fn flush(&self) -> Result<usize> {
+        Ok(self.db.flush_async().await?)
+    }
+
+    /// In-memory store that's thrown away when dropped.
+    #[cfg(test)]
+    pub fn temporary() -> Result<Self> {
+        Self::from_db(sled::Config::new().temporary(true).open()?)
+    }
+
+    fn from_db(db: sled::Db) -> Result<Self> {
+        Ok(Self {
+            meta: db.open_tree("meta")?,
+            names: db.open_tree("names")?,
+            seen_logs: db.open_tree("seen_logs")?,
+            positions: db.open_tree("positions")?,
+            audit: db.open_tree("audit")?,
+            funded: db.open_tree("funded")?,
+            prices: db.open_tree("prices")?,
+            trades: db.open_tree("trades")?,
+            games: db.open_tree("games")?,
+            candles: db.open_tree("candles")?,
+            db,
+        })
+    }
+
+    pub fn load_names(&self) -> Result<HashMap<Address, String>> {
+        let mut names = HashMap::new();
+        for entry in self.names.iter() {
+            let (key, value) = entry?;
+            names.insert(
+                Address::from_slice(&key),
+                String::from_utf8(value.to_vec())?,
+            );
+        }
+        Ok(names)
+    }
+
+    pub fn set_name(&self, address: Address, name: &str) -> Result<()> {
+        self.names.insert(address.as_slice(), name.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn load_seen_logs(&self) -> Result<HashSet<(TxHash, u64)>> {
+        let mut seen = HashSet::new();
+        for key in self.seen_logs.iter().keys() {
+            let key = key?;
+            let tx_hash = TxHash::from_slice(&key[..32]);
+            let log_index = u64::from_be_bytes(key[32..40].try_into()?);
+            seen.insert((tx_hash, log_index));
+        }
+        Ok(seen)
+    }
+
+    pub fn insert_seen_log(&self, (tx_hash, log_index): (TxHash, u64)) -> Result<()> {
+        let mut key = [0u8; 40];
+        key[..32].copy_from_slice(tx_hash.as_slice());
+        key[32..].copy_from_slice(&log_index.to_be_bytes());
+        self.seen_logs.insert(key, &[])?;
+        Ok(())
+    }
+
+    /// Returns `(balances, holdings)`.
+    pub fn load_positions(&self) -> Result<(HashMap<Address, u64>, HashMap<Address, u64>)> {
+        let mut balances = HashMap::new();
+        let mut holdings = HashMap::new();
+        for entry in self.positions.iter() {
+            let (key, value) = entry?;
+            let address = Address::from_slice(&key);
+            balances.insert(address, u64::from_be_bytes(value[..8].try_into()?));
+            holdings.insert(address, u64::from_be_bytes(value[8..16].try_into()?));
+        }
+        Ok((balances, holdings))
+    }
+
+    pub fn set_position(&self, address: Address, balance: u64, holdings: u64) -> Result<()> {
+        let mut value = [0u8; 16];
+        value[..8].copy_from_slice(&balance.to_be_bytes());
+        value[8..].copy_from_slice(&holdings.to_be_bytes());
+        self.positions.insert(address.as_slice(), &value)?;
+        Ok(())
+    }
+
+    pub fn is_funded(&self, address: Address) -> Result<bool> {
+        Ok(self.funded.contains_key(address.as_slice())?)
+    }
+
+    /// Faucet ledger entry: 32 bytes big-endian amount in wei, 8 bytes unix
+    /// seconds. Entries written before amounts were U256 are 8 + 8 bytes.
+    pub fn record_funding(&self, address: Address, amount: U256) -> Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let mut value = [0u8; 40];
+        value[..32].copy_from_slice(&amount.to_be_bytes::<32>());
+        value[32..].copy_from_slice(&timestamp.to_be_bytes());
+        self.funded.insert(address.as_slice(), &value[..])?;
+        Ok(())
+    }
+
+    /// Price series of the running game, keyed by big-endian block number.
+    pub fn record_price(&self, block_number: u64, price: u64) -> Result<()> {
+        self.prices
+            .insert(block_number.to_be_bytes(), &price.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// Returns `(block_number, price)` pairs for blocks in `from..=to`, in
+    /// block order.
+    pub fn load_prices(&self, from: u64, to: u64) -> Result<Vec<(u64, u64)>> {
+        let mut prices = Vec::new();
+        if from > to {
+            return Ok(prices);
+        }
+        for entry in self.prices.range(from.to_be_bytes()..=to.to_be_bytes()) {
+            let (key, value) = entry?;
+            prices.push((
+                u64::from_be_bytes(key[..8].try_into()?),
+                u64::from_be_bytes(value[..8].try_into()?),
+            ));
+        }
+        Ok(prices)
+    }
+
+    /// Trade ledger, JSON keyed by address, big-endian block number and log
+    /// index so a player's trades are contiguous and in order.
+    pub fn insert_trade(
+        &self,
+        address: Address,
+        block_number: u64,
+        log_index: u64,
+        trade: &impl Serialize,
+    ) -> Result<()> {
+        let mut key = [0u8; 36];
+        key[..20].copy_from_slice(address.as_slice());
+        key[20..28].copy_from_slice(&block_number.to_be_bytes());
+        key[28..].copy_from_slice(&log_index.to_be_bytes());
+        self.trades.insert(key, serde_json::to_vec(trade)?)?;
+        Ok(())
+    }
+
+    pub fn load_trades<R: DeserializeOwned>(&self, address: Address) -> Result<Vec<R>> {
+        let mut trades = Vec::new();
+        for value in self.trades.scan_prefix(address.as_slice()).values() {
+            trades.push(serde_json::from_slice(&value?)?);
+        }
+        Ok(trades)
+    }
+
+    pub fn load_all_trades<R: DeserializeOwned>(&self) -> Result<Vec<R>> {
+        let mut trades = Vec::new();
+        for value in self.trades.iter().values() {
+            trades.push(serde_json::from_slice(&value?)?);
+        }
+        Ok(trades)
+    }
+
+    /// Forgets the running game's prices when a new one starts.
+    pub fn clear_game(&self) -> Result<()> {
+        self.prices.clear()?;
+        Ok(())
+    }
+
+    /// Archived games are JSON, keyed by big-endian game id.
+    pub fn insert_game(&self, id: u64, record: &impl Serialize) -> Result<()> {
+        self.games
+            .insert(id.to_be_bytes(), serde_json::to_vec(record)?)?;
+        Ok(())
+    }
+
+    pub fn load_game<R: DeserializeOwned>(&self, id: u64) -> Result<Option<R>> {
+        match self.games.get(id.to_be_bytes())? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn load_games<R: DeserializeOwned>(&self) -> Result<Vec<R>> {
+        let mut games = Vec::new();
+        for value in self.games.iter().values() {
+            games.push(serde_json::from_slice(&value?)?);
+        }
+        Ok(games)
+    }
+
+    /// Closed candles are JSON, keyed by big-endian window then start block.
+    pub fn insert_candle(
+        &self,
+        window: u64,
+        start_block: u64,
+        candle: &impl Serialize,
+    ) -> Result<()> {
+        let mut key = [0u8; 16];
+        key[..8].copy_from_slice(&window.to_be_bytes());
+        key[8..].copy_from_slice(&start_block.to_be_bytes());
+        self.candles.insert(key, serde_json::to_vec(candle)?)?;
+        Ok(())
+    }
+
+    /// Closed candles for `window` starting in `from..=to`, oldest first.
+    pub fn load_candles<C: DeserializeOwned>(
+        &self,
+        window: u64,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<C>> {
+        let key = |start_block: u64| {
+            let mut key = [0u8; 16];
+            key[..8].copy_from_slice(&window.to_be_bytes());
+            key[8..].copy_from_slice(&start_block.to_be_bytes());
+            key
+        };
+        let mut candles = Vec::new();
+        if from > to {
+            return Ok(candles);
+        }
+        for value in self.candles.range(key(from)..=key(to)).values() {
+            candles.push(serde_json::from_slice(&value?)?);
+        }
+        Ok(candles)
+    }
+
+    /// Appends one JSON record to the admin audit log, in insertion order.
+    pub fn append_audit(&self, entry: &impl Serialize) -> Result<()> {
+        let id = self.db.generate_id()?;
+        self.audit
+            .insert(id.to_be_bytes(), serde_json::to_vec(entry)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deployment_is_recorded_on_first_open() {
+        let store = Store::temporary().unwrap();
+        let contract = Address::repeat_byte(1);
+        store.check_deployment(contract, 10143).unwrap();
+        store.check_deployment(contract, 10143).unwrap();
+
+        let err = store.check_deployment(Address::repeat_byte(2), 10143);
+        assert!(err.unwrap_err().to_string().contains("belongs to contract"));
+        assert!(store.check_deployment(contract, 1).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{AppState, BackendTxEvent, archive, backfill, chain_events, ws::ServerMessage};
+use alloy::{
+    primitives::Address,
+    providers::{Provider, ProviderBuilder, WsConnect},
+    rpc::types::{Filter, Log},
+    transports::Transport,
+};
+use anyhow::Result;
+use futures_util::StreamExt;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::{RwLock, broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Owns the read-side RPC connection: new heads drive ticks and game over,
+/// `monadLogs` drive `AppState`. When either subscription drops, the
+/// connection is rebuilt with exponential backoff and the blocks missed in
+/// between are backfilled from `current_block_height`.
+pub struct ChainSubscriber {
+    rpc_url: String,
+    contract_addr: Address,
+    state: Arc<RwLock<AppState>>,
+    broadcast_tx: broadcast::Sender<ServerMessage>,
+    backend_tx_sender: mpsc::Sender<BackendTxEvent>,
+    shutdown: CancellationToken,
+    last_ended_block: u64,
+    last_archived_block: u64,
+}
+
+impl ChainSubscriber {
+    pub fn new(
+        rpc_url: String,
+        contract_addr: Address,
+        state: Arc<RwLock<AppState>>,
+        broadcast_tx: broadcast::Sender<ServerMessage>,
+        backend_tx_sender: mpsc::Sender<BackendTxEvent>,
+        shutdown: CancellationToken,
+    ) -> Self {
+        Self {
+            rpc_url,
+            contract_addr,
+            state,
+            broadcast_tx,
+            backend_tx_sender,
+            shutdown,
+            last_ended_block: 0,
+            last_archived_block: 0,
+        }
+    }
+
+    /// Runs until shutdown. `backfill_from` is only used for the first
+    /// connection; later ones resume from the last block seen.
+    pub async fn run(mut self, mut backfill_from: Option<u64>) -> Result<()> {
+        let shutdown = self.shutdown.clone();
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            match self.session(backfill_from, &mut backoff).await {
+                Ok(()) if shutdown.is_cancelled() => return Ok(()),
+                Ok(()) => tracing::warn!("⚠️  RPC subscription stream ended"),
+                Err(e) => tracing::error!("RPC subscription error: {}", e),
+            }
+
+            let watermark = self.state.read().await.current_block_height;
+            if watermark > 0 {
+                backfill_from = Some(watermark);
+            }
+
+            tracing::info!("🔌 Reconnecting to RPC in {:?}...", backoff);
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                _ = shutdown.cancelled() => return Ok(()),
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    async fn session(&mut self, backfill_from: Option<u64>, backoff: &mut Duration) -> Result<()> {
+        let provider = ProviderBuilder::new()
+            .on_ws(WsConnect::new(&self.rpc_url))
+            .await?;
+
+        tracing::info!("Starting block subscriber...");
+        let mut block_stream = provider.subscribe_blocks().await?.into_stream();
+
+        tracing::info!("Starting event listener (using monadLogs for lower latency)...");
+        let filter = Filter::new().address(self.contract_addr);
+        // Only Monad nodes speak monadLogs; anything else (e.g. anvil) gets
+        // the standard logs subscription.
+        let mut log_stream = match provider
+            .client()
+            .request::<_, String>("eth_subscribe", ("monadLogs", &filter))
+            .await
+        {
+            Ok(subscription_id) => provider
+                .get_subscription::<Log>(subscription_id.parse()?)
+                .await?
+                .into_stream(),
+            Err(e) => {
+                tracing::warn!("⚠️  monadLogs unavailable ({}), subscribing to logs", e);
+                provider.subscribe_logs(&filter).await?.into_stream()
+            }
+        };
+
+        tracing::info!("Subscribed to contract logs and blocks!");
+        *backoff = INITIAL_BACKOFF;
+
+        // The subscriptions are already buffering, so anything emitted while
+        // we backfill is picked up afterwards and deduplicated via seen_logs.
+        match backfill_from {
+            Some(from_block) => {
+                let head = provider.get_block_number().await?;
+                backfill::backfill_logs(
+                    &provider,
+                    self.contract_addr,
+                    from_block,
+                    head,
+                    &self.state,
+                    &self.broadcast_tx,
+                )
+                .await?;
+            }
+            None => {
+                tracing::info!("No game started and BACKFILL_FROM_BLOCK unset, skipping backfill");
+            }
+        }
+
+        // Shutdown is only checked between events, so a log is never left
+        // half-applied.
+        loop {
+            tokio::select! {
+                _ = self.shutdown.cancelled() => return Ok(()),
+                block = block_stream.next() => match block {
+                    Some(block) => {
+                        self.handle_block(&provider, block.number, block.timestamp)
+                            .await
+                    }
+                    None => {
+                        tracing::warn!("⚠️  Block subscription closed");
+                        return Ok(());
+                    }
+                },
+                log = log_stream.next() => match log {
+                    Some(log) => {
+                        if let Err(e) =
+                            chain_events::handle_log(&log, &self.state, &self.broadcast_tx).await
+                        {
+                            tracing::error!("Failed to process log: {}", e);
+                        }
+                    }
+                    None => {
+                        tracing::warn!("⚠️  Log subscription closed");
+                        return Ok(());
+                    }
+                },
+            }
+        }
+    }
+
+    async fn handle_block<T, P>(&mut self, provider: &P, block_number: u64, timestamp: u64)
+    where
+        T: Transport + Clone,
+        P: Provider<T>,
+    {
+        tracing::info!("🧱 New Block: {} (timestamp: {})", block_number, timestamp);
+
+        let (game_start_block, game_end_block) = {
+            let mut state_guard = self.state.write().await;
+            state_guard.current_block_height = block_number;
+            (state_guard.game_start_block, state_guard.game_end_block)
+        };
+
+        if let Some(ends_at) = game_end_block {
+            if ends_at > self.last_ended_block && block_number >= ends_at {
+                self.last_ended_block = ends_at;
+                let _ = self.backend_tx_sender.send(BackendTxEvent::GameOver).await;
+            }
+            // Once the last block is final, i.e. the next one has arrived.
+            if ends_at > self.last_archived_block && block_number > ends_at {
+                self.archive(provider, game_start_block.unwrap_or(ends_at), ends_at)
+                    .await;
+            }
+            if ends_at > block_number {
+                tracing::info!("⏰ Auto-tick triggered on block {}", block_number);
+                let _ = self.backend_tx_sender.send(BackendTxEvent::Tick).await;
+            }
+        }
+    }
+
+    /// Headers can run ahead of the log stream, so the game's logs are
+    /// fetched (and deduplicated) up to its last block before the snapshot is
+    /// taken. If that fails the archive is retried on the next block. The
+    /// archived leaderboard is what clients get as the final standings, so it
+    /// matches `/api/games`.
+    async fn archive<T, P>(&mut self, provider: &P, start_block: u64, end_block: u64)
+    where
+        T: Transport + Clone,
+        P: Provider<T>,
+    {
+        if let Err(e) = backfill::backfill_logs(
+            provider,
+            self.contract_addr,
+            start_block,
+            end_block,
+            &self.state,
+            &self.broadcast_tx,
+        )
+        .await
+        {
+            tracing::error!("Failed to catch up on logs before archiving: {}", e);
+            return;
+        }
+
+        self.last_archived_block = end_block;
+        match archive::archive_game(&self.state).await {
+            Ok(Some(record)) => {
+                tracing::info!(
+                    "🗄️  Archived game {} ({} players)",
+                    record.id,
+                    record.leaderboard.len()
+                );
+                let _ = self.broadcast_tx.send(ServerMessage::FinalStandings {
+                    entries: record.leaderboard,
+                });
+            }
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to archive game: {}", e),
+        }
+    }
+}
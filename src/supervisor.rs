@@ -0,0 +1,139 @@
+use anyhow::{Result, anyhow};
+use std::{future::Future, time::Duration};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Owns the server's long-running tasks. Helpers are restarted when they
+/// stop; critical tasks bring the whole server down instead, since e.g. an
+/// executor that died would leave clients queueing funds nobody sends.
+pub struct Supervisor {
+    shutdown: CancellationToken,
+    critical: JoinSet<(&'static str, Result<()>)>,
+}
+
+impl Supervisor {
+    pub fn new(shutdown: CancellationToken) -> Self {
+        Self {
+            shutdown,
+            critical: JoinSet::new(),
+        }
+    }
+
+    /// Runs `make()` until shutdown, starting it again with backoff whenever
+    /// it returns or panics.
+    pub fn spawn_restarting<F, Fut>(&mut self, name: &'static str, make: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            let mut backoff = INITIAL_BACKOFF;
+            loop {
+                let mut task = tokio::spawn(make());
+                tokio::select! {
+                    result = &mut task => match result {
+                        Ok(()) => tracing::warn!("⚠️  {} stopped", name),
+                        Err(e) => tracing::error!("💥 {} panicked: {}", name, e),
+                    },
+                    _ = shutdown.cancelled() => {
+                        task.abort();
+                        return;
+                    }
+                }
+
+                tracing::info!("🔁 Restarting {} in {:?}", name, backoff);
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = shutdown.cancelled() => return,
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        });
+    }
+
+    /// Runs a task the server can't do without. It is expected to return
+    /// only after shutdown was requested; stopping any earlier shuts
+    /// everything down.
+    pub fn spawn_critical(
+        &mut self,
+        name: &'static str,
+        task: impl Future<Output = Result<()>> + Send + 'static,
+    ) {
+        self.critical.spawn(async move { (name, task.await) });
+    }
+
+    /// Waits for a shutdown request or the first critical task to stop. In
+    /// the latter case shutdown is triggered and the task's error returned.
+    pub async fn wait(&mut self) -> Result<()> {
+        tokio::select! {
+            _ = self.shutdown.cancelled() => Ok(()),
+            Some(joined) = self.critical.join_next() => {
+                let error = match joined {
+                    Ok((name, Ok(()))) => anyhow!("{} stopped unexpectedly", name),
+                    Ok((name, Err(e))) => e.context(format!("{} failed", name)),
+                    Err(e) => anyhow!("critical task panicked: {}", e),
+                };
+                tracing::error!("💀 {:#}, shutting down", error);
+                self.shutdown.cancel();
+                Err(error)
+            }
+        }
+    }
+
+    /// Gives the critical tasks `grace` to wind down after shutdown, then
+    /// aborts whatever is left.
+    pub async fn drain(mut self, grace: Duration) {
+        let drained = tokio::time::timeout(grace, async {
+            while let Some(joined) = self.critical.join_next().await {
+                match joined {
+                    Ok((name, Ok(()))) => tracing::info!("✅ {} stopped", name),
+                    Ok((name, Err(e))) => tracing::error!("{} failed during shutdown: {}", name, e),
+                    Err(e) => tracing::error!("Critical task panicked during shutdown: {}", e),
+                }
+            }
+        })
+        .await;
+        if drained.is_err() {
+            tracing::warn!(
+                "⚠️  {} tasks still running after {:?}, aborting",
+                self.critical.len(),
+                grace
+            );
+            self.critical.shutdown().await;
+        }
+    }
+}
+
+/// Resolves on SIGINT or SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("🛑 SIGINT received"),
+        _ = terminate => tracing::info!("🛑 SIGTERM received"),
+    }
+}
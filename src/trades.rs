@@ -0,0 +1,121 @@
+use crate::{
+    AppState,
+    ws::{Trade, TradeSide},
+};
+use alloy::primitives::{Address, TxHash};
+use anyhow::Result;
+
+/// Position a freshly registered player starts from (`INITIAL_CREDITS` and
+/// `INITIAL_STOCKS` in StockMarket.sol), used when we haven't seen one yet.
+const INITIAL_POSITION: (u64, u64) = (500, 10);
+
+/// Works out the trade behind a position change. buy/sell move holdings by
+/// `amount` and the balance by `amount * price`, so the execution price falls
+/// out of the ratio.
+pub fn diff(
+    address: Address,
+    previous: Option<(u64, u64)>,
+    (balance, holdings): (u64, u64),
+    block_number: u64,
+    tx_hash: TxHash,
+) -> Option<Trade> {
+    let (previous_balance, previous_holdings) = previous.unwrap_or(INITIAL_POSITION);
+    let side = match holdings.cmp(&previous_holdings) {
+        std::cmp::Ordering::Greater => TradeSide::Buy,
+        std::cmp::Ordering::Less => TradeSide::Sell,
+        std::cmp::Ordering::Equal => return None,
+    };
+    let quantity = holdings.abs_diff(previous_holdings);
+    Some(Trade {
+        address: format!("{:?}", address),
+        side,
+        quantity,
+        price: balance.abs_diff(previous_balance) / quantity,
+        block_number,
+        tx_hash: format!("{:?}", tx_hash),
+    })
+}
+
+/// Profit of `trades` marked to `price`: cash received minus cash spent, plus
+/// the value of the shares still held from them.
+pub fn pnl(trades: &[Trade], price: u64) -> i64 {
+    trades
+        .iter()
+        .map(|trade| {
+            let value = (trade.quantity * trade.price) as i64;
+            let marked = (trade.quantity * price) as i64;
+            match trade.side {
+                TradeSide::Buy => marked - value,
+                TradeSide::Sell => value - marked,
+            }
+        })
+        .sum()
+}
+
+/// `address`'s trades in the current game (all of them if no game has
+/// started) and their P&L at the current price.
+pub fn current_game(state: &AppState, address: Address) -> Result<(Vec<Trade>, i64)> {
+    let from = state.game_start_block.unwrap_or(0);
+    let to = state.game_end_block.unwrap_or(u64::MAX);
+    between(state, address, from, to)
+}
+
+pub fn between(
+    state: &AppState,
+    address: Address,
+    from_block: u64,
+    to_block: u64,
+) -> Result<(Vec<Trade>, i64)> {
+    let trades: Vec<Trade> = state
+        .store
+        .load_trades::<Trade>(address)?
+        .into_iter()
+        .filter(|trade| (from_block..=to_block).contains(&trade.block_number))
+        .collect();
+    let pnl = pnl(&trades, state.current_price);
+    Ok((trades, pnl))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: Address = Address::repeat_byte(0xa1);
+
+    fn trade(previous: Option<(u64, u64)>, position: (u64, u64)) -> Option<Trade> {
+        diff(ALICE, previous, position, 7, TxHash::ZERO)
+    }
+
+    #[test]
+    fn buy_from_the_initial_position() {
+        let trade = trade(None, (350, 13)).unwrap();
+        assert_eq!(trade.side, TradeSide::Buy);
+        assert_eq!((trade.quantity, trade.price), (3, 50));
+        assert_eq!(trade.block_number, 7);
+    }
+
+    #[test]
+    fn sell_from_a_known_position() {
+        let trade = trade(Some((350, 13)), (470, 11)).unwrap();
+        assert_eq!(trade.side, TradeSide::Sell);
+        assert_eq!((trade.quantity, trade.price), (2, 60));
+    }
+
+    #[test]
+    fn unchanged_holdings_are_no_trade() {
+        assert!(trade(None, (500, 10)).is_none());
+        assert!(trade(Some((350, 13)), (350, 13)).is_none());
+    }
+
+    #[test]
+    fn pnl_marks_buys_and_sells_to_the_price() {
+        let trades = [
+            trade(None, (350, 13)).unwrap(),
+            trade(Some((350, 13)), (470, 11)).unwrap(),
+        ];
+        // Bought 3 @ 50 and sold 2 @ 60: +15 + 10 at 55, -30 + 40 at 40.
+        assert_eq!(pnl(&trades, 55), 25);
+        assert_eq!(pnl(&trades, 40), 10);
+        assert_eq!(pnl(&[], 55), 0);
+    }
+}
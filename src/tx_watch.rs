@@ -0,0 +1,161 @@
+use crate::ws::ServerMessage;
+use alloy::{
+    eips::BlockId,
+    primitives::TxHash,
+    providers::Provider,
+    rpc::types::{Transaction, TransactionReceipt},
+    sol_types::{Revert, SolError},
+    transports::Transport,
+};
+use anyhow::Result;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+// How often to ask the node whether it still knows the tx at all.
+const MEMPOOL_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+// Consecutive "unknown tx" answers before we call it dropped.
+const DROPPED_AFTER_MISSES: u32 = 5;
+const WATCH_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// `require` messages in StockMarket.sol, used to recognise the reason when a
+/// node only reports it inside the error text.
+const KNOWN_REVERT_REASONS: &[&str] = &[
+    "Insufficient credits",
+    "Insufficient holdings",
+    "Contract has ended",
+    "Contract not started",
+    "Not registered",
+    "Amount must be greater than 0",
+    "Already ticked this block",
+    "Contract ended",
+    "Only owner",
+];
+
+/// Follows a client-submitted tx until it is mined or dropped and reports the
+/// outcome back to that client only.
+pub async fn watch_client_tx<T, P>(
+    provider: P,
+    tx_hash: TxHash,
+    client_tx: mpsc::Sender<ServerMessage>,
+) where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let msg = match wait_for_outcome(&provider, tx_hash).await {
+        Ok(Some(msg)) => msg,
+        Ok(None) => {
+            tracing::warn!("⚠️  Stopped watching {:?}, still pending", tx_hash);
+            return;
+        }
+        Err(e) => {
+            tracing::error!("Failed to watch tx {:?}: {}", tx_hash, e);
+            return;
+        }
+    };
+    let _ = client_tx.send(msg).await;
+}
+
+async fn wait_for_outcome<T, P>(provider: &P, tx_hash: TxHash) -> Result<Option<ServerMessage>>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let started = Instant::now();
+    let mut last_mempool_check = Instant::now();
+    let mut misses = 0;
+
+    while started.elapsed() < WATCH_TIMEOUT {
+        if let Some(receipt) = provider.get_transaction_receipt(tx_hash).await? {
+            return Ok(Some(outcome(provider, receipt).await));
+        }
+
+        if last_mempool_check.elapsed() >= MEMPOOL_CHECK_INTERVAL {
+            last_mempool_check = Instant::now();
+            if provider.get_transaction_by_hash(tx_hash).await?.is_some() {
+                misses = 0;
+            } else {
+                misses += 1;
+                if misses >= DROPPED_AFTER_MISSES {
+                    tracing::warn!("🗑️  Client tx dropped: {:?}", tx_hash);
+                    return Ok(Some(ServerMessage::TxDropped {
+                        tx_hash: format!("{:?}", tx_hash),
+                    }));
+                }
+            }
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    Ok(None)
+}
+
+async fn outcome<T, P>(provider: &P, receipt: TransactionReceipt) -> ServerMessage
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let tx_hash = receipt.transaction_hash;
+    let block_number = receipt.block_number.unwrap_or_default();
+
+    if receipt.status() {
+        tracing::info!(
+            "✅ Client tx confirmed: {:?} (block {})",
+            tx_hash,
+            block_number
+        );
+        return ServerMessage::TxConfirmed {
+            tx_hash: format!("{:?}", tx_hash),
+            block_number,
+            gas_used: receipt.gas_used as u64,
+        };
+    }
+
+    let reason = match provider.get_transaction_by_hash(tx_hash).await {
+        Ok(Some(tx)) => revert_reason(provider, tx, block_number).await,
+        Ok(None) => None,
+        Err(e) => {
+            tracing::debug!("Could not fetch reverted tx {:?}: {}", tx_hash, e);
+            None
+        }
+    };
+    let reason = reason.unwrap_or_else(|| "unknown".to_string());
+    tracing::info!("❌ Client tx reverted: {:?} ({})", tx_hash, reason);
+
+    ServerMessage::TxReverted {
+        tx_hash: format!("{:?}", tx_hash),
+        reason,
+    }
+}
+
+/// Replays the tx as an `eth_call` on top of the parent block to recover the
+/// revert string. Close enough for our contract, whose requires only depend on
+/// state that rarely changes within one block.
+async fn revert_reason<T, P>(provider: &P, tx: Transaction, block_number: u64) -> Option<String>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let request = tx.into_request();
+    let err = provider
+        .call(&request)
+        .block(BlockId::number(block_number.saturating_sub(1)))
+        .await
+        .err()?;
+
+    if let Some(reason) = err
+        .as_error_resp()
+        .and_then(|payload| payload.as_revert_data())
+        .and_then(|data| Revert::abi_decode(&data, true).ok())
+        .map(|revert| revert.reason().to_string())
+    {
+        return Some(reason);
+    }
+
+    let message = err.to_string();
+    KNOWN_REVERT_REASONS
+        .iter()
+        .find(|reason| message.contains(*reason))
+        .map(|reason| reason.to_string())
+        .or(Some(message))
+}
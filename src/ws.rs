@@ -3,9 +3,32 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ClientMessage {
-    SetName { name: String, address: String },
-    RawTx { raw_tx: String },
-    GetNonce { address: String },
+    SetName {
+        name: String,
+        address: String,
+        signature: String,
+    },
+    RawTx {
+        raw_tx: String,
+    },
+    GetNonce {
+        address: String,
+    },
+    RequestFunds {
+        address: String,
+        signature: String,
+    },
+    SolveChallenge {
+        address: String,
+        solution: u64,
+    },
+    GetPriceHistory {
+        from_block: u64,
+        to_block: u64,
+    },
+    GetTrades {
+        address: String,
+    },
     RestartGame,
 }
 
@@ -14,6 +37,10 @@ pub enum ClientMessage {
 pub enum ServerMessage {
     ConnectionInfo {
         contract_address: String,
+        session: String,
+        gas_costs: GasInfo,
+    },
+    GasCosts {
         gas_costs: GasInfo,
     },
     PriceUpdate {
@@ -23,10 +50,19 @@ pub enum ServerMessage {
     CurrentPrice {
         price: u64,
     },
+    PriceHistory {
+        points: Vec<PricePoint>,
+    },
     NameSet {
         address: String,
         name: String,
     },
+    NameError {
+        address: String,
+        name: String,
+        error: String,
+        reason: NameErrorReason,
+    },
     Position {
         address: String,
         balance: u64,
@@ -35,27 +71,72 @@ pub enum ServerMessage {
     },
     TxError {
         error: String,
+        reason: TxErrorReason,
     },
     NonceResponse {
         address: String,
         nonce: u64,
     },
+    FundQueued {
+        address: String,
+    },
+    FundPending {
+        address: String,
+        tx_hash: String,
+    },
     Funded {
         address: String,
-        amount: u64,
+        /// Wei as a decimal string, balances don't fit in a u64
+        amount: String,
     },
     FundError {
         address: String,
         error: String,
+        reason: FundErrorReason,
+    },
+    FundChallenge {
+        address: String,
+        challenge: String,
+        difficulty: u8,
     },
     TxSubmitted {
         tx_hash: String,
     },
+    TxConfirmed {
+        tx_hash: String,
+        block_number: u64,
+        gas_used: u64,
+    },
+    TxReverted {
+        tx_hash: String,
+        reason: String,
+    },
+    TxDropped {
+        tx_hash: String,
+    },
+    Unauthorized {
+        action: String,
+        error: String,
+    },
     GameStarted {
         start_height: u64,
         end_height: u64,
     },
     GameEnded,
+    Leaderboard {
+        entries: Vec<LeaderboardEntry>,
+    },
+    CandleClosed {
+        candle: Candle,
+    },
+    Trades {
+        address: String,
+        trades: Vec<Trade>,
+        pnl: i64,
+    },
+    FinalStandings {
+        entries: Vec<LeaderboardEntry>,
+    },
     CurrentBlockHeight {
         height: u64,
     },
@@ -67,3 +148,88 @@ pub struct GasInfo {
     pub buy: u64,
     pub sell: u64,
 }
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PricePoint {
+    pub block_number: u64,
+    pub price: u64,
+}
+
+/// OHLC bar over `window` blocks, `start_block..=end_block`. Volume is the
+/// number of shares bought or sold.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candle {
+    pub window: u64,
+    pub start_block: u64,
+    pub end_block: u64,
+    pub open: u64,
+    pub high: u64,
+    pub low: u64,
+    pub close: u64,
+    pub volume: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trade {
+    pub address: String,
+    pub side: TradeSide,
+    pub quantity: u64,
+    pub price: u64,
+    pub block_number: u64,
+    pub tx_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub address: String,
+    pub name: Option<String>,
+    pub net_worth: u64,
+    pub rank: u32,
+}
+
+/// Why a raw tx was not relayed, so clients can react without parsing `error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxErrorReason {
+    Malformed,
+    WrongChainId,
+    WrongRecipient,
+    UnknownSelector,
+    InvalidSignature,
+    InsufficientBalance,
+    /// The node refused the tx (nonce too low, underpriced, ...)
+    Rejected,
+    Rpc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameErrorReason {
+    InvalidSignature,
+    InvalidLength,
+    InvalidCharacters,
+    Taken,
+    /// Refused by the name filter
+    Rejected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FundErrorReason {
+    InvalidSignature,
+    AlreadyFunded,
+    ConnectionLimit,
+    IpLimit,
+    BudgetExhausted,
+    ChallengeFailed,
+    /// The funding tx was sent but reverted or could not be sent
+    TxFailed,
+    Unavailable,
+}
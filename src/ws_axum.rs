@@ -1,35 +1,58 @@
-use crate::{ws::*, AppState, BackendTxEvent, GasCosts};
+use crate::{BackendTxEvent, ServerState, metrics::METRICS, ws::*};
 use alloy::{
-    primitives::{Address, Bytes},
+    primitives::{Address, B256, Bytes},
     providers::{Provider, WalletProvider},
     transports::Transport,
 };
 use anyhow::Result;
-use axum::extract::ws::{Message as AxumMessage, WebSocket};
+use axum::extract::ws::{CloseFrame, Message as AxumMessage, WebSocket, close_code};
 use futures_util::{SinkExt, StreamExt};
-use std::sync::Arc;
-use tokio::sync::{broadcast, mpsc, RwLock};
+use std::net::{IpAddr, SocketAddr};
+use tokio::sync::mpsc;
 
-pub async fn handle_axum_connection<T, P>(
+/// Who is on the other end of a websocket, decided at upgrade time.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ClientInfo {
+    pub peer: SocketAddr,
+    pub ip: IpAddr,
+    pub is_admin: bool,
+}
+
+pub(crate) async fn handle_axum_connection<T, P>(
     socket: WebSocket,
-    state: Arc<RwLock<AppState>>,
-    mut broadcast_rx: broadcast::Receiver<ServerMessage>,
-    broadcast_tx: broadcast::Sender<ServerMessage>,
-    provider: P,
-    gas_costs: Arc<GasCosts>,
-    contract_address: Address,
-    backend_tx_sender: mpsc::Sender<BackendTxEvent>,
+    server_state: ServerState<T, P>,
+    client: ClientInfo,
 ) -> Result<()>
 where
     T: Transport + Clone,
     P: Provider<T> + WalletProvider + Clone + 'static,
 {
+    let ServerState {
+        app_state: state,
+        broadcast_tx,
+        provider,
+        gas_costs,
+        contract_address,
+        chain_id,
+        backend_tx_sender,
+        backend,
+        chain,
+        name_rules,
+        shutdown,
+        tasks,
+        ..
+    } = server_state;
+    let mut broadcast_rx = broadcast_tx.subscribe();
+    // Signed by the client to prove address ownership on this connection.
+    let session = format!("{:?}", B256::from(rand::random::<[u8; 32]>()));
     let (client_tx, mut client_rx) = mpsc::channel::<ServerMessage>(100);
     let (mut ws_sender, mut ws_receiver) = socket.split();
 
     {
+        let gas_costs = gas_costs.read().await.clone();
         let connection_info = ServerMessage::ConnectionInfo {
             contract_address: format!("{:?}", contract_address),
+            session: session.clone(),
             gas_costs: GasInfo {
                 register: gas_costs.register,
                 buy: gas_costs.buy,
@@ -49,6 +72,12 @@ where
         ws_sender.send(AxumMessage::Text(json)).await?;
         tracing::info!("Sent current price {} to client", state_guard.current_price);
 
+        let price_history_msg = ServerMessage::PriceHistory {
+            points: state_guard.price_history.iter().copied().collect(),
+        };
+        let json = serde_json::to_string(&price_history_msg)?;
+        ws_sender.send(AxumMessage::Text(json)).await?;
+
         let current_block_msg = ServerMessage::CurrentBlockHeight {
             height: state_guard.current_block_height,
         };
@@ -110,54 +139,127 @@ where
             let json = serde_json::to_string(&msg)?;
             ws_sender.send(AxumMessage::Text(json)).await?;
         }
+
+        let msg = ServerMessage::Leaderboard {
+            entries: crate::leaderboard::compute(&state_guard),
+        };
+        let json = serde_json::to_string(&msg)?;
+        ws_sender.send(AxumMessage::Text(json)).await?;
     }
 
+    // Faucet bookkeeping for this connection.
+    let mut fund_requests = 0;
+    let mut pending_challenge: Option<(Address, B256)> = None;
+
     let state_clone = state.clone();
+    let send_shutdown = shutdown.clone();
     let send_task = tokio::spawn(async move {
         loop {
             tokio::select! {
+                _ = send_shutdown.cancelled() => {
+                    let frame = CloseFrame {
+                        code: close_code::AWAY,
+                        reason: "Server shutting down".into(),
+                    };
+                    let _ = ws_sender.send(AxumMessage::Close(Some(frame))).await;
+                    break;
+                }
                 Ok(msg) = broadcast_rx.recv() => {
-                    if let Ok(json) = serde_json::to_string(&msg) {
-                        if ws_sender.send(AxumMessage::Text(json)).await.is_err() {
-                            break;
-                        }
+                    if let Ok(json) = serde_json::to_string(&msg)
+                        && ws_sender.send(AxumMessage::Text(json)).await.is_err()
+                    {
+                        break;
                     }
                 }
                 Some(msg) = client_rx.recv() => {
-                    if let Ok(json) = serde_json::to_string(&msg) {
-                        if ws_sender.send(AxumMessage::Text(json)).await.is_err() {
-                            break;
-                        }
+                    if let Ok(json) = serde_json::to_string(&msg)
+                        && ws_sender.send(AxumMessage::Text(json)).await.is_err()
+                    {
+                        break;
                     }
                 }
             }
         }
     });
 
-    while let Some(msg) = ws_receiver.next().await {
+    loop {
+        let msg = tokio::select! {
+            msg = ws_receiver.next() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            _ = shutdown.cancelled() => break,
+        };
         match msg {
             Ok(AxumMessage::Text(text)) => {
                 tracing::debug!("Received WebSocket message: {}", text);
                 match serde_json::from_str::<ClientMessage>(&text) {
                     Ok(client_msg) => match client_msg {
-                        ClientMessage::SetName { name, address } => {
-                            match address.parse::<Address>() {
-                                Ok(addr) => {
-                                    tracing::info!("Setting name: {} → {}", address, name);
+                        ClientMessage::SetName {
+                            name,
+                            address,
+                            signature,
+                        } => {
+                            let addr = match address.parse::<Address>() {
+                                Ok(addr) => addr,
+                                Err(e) => {
+                                    tracing::error!("Failed to parse address '{}': {}", address, e);
+                                    continue;
+                                }
+                            };
+                            let reject =
+                                |error: String, reason: NameErrorReason| ServerMessage::NameError {
+                                    address: format!("{:?}", addr),
+                                    name: name.clone(),
+                                    error,
+                                    reason,
+                                };
 
-                                    {
-                                        let mut state_guard = state_clone.write().await;
-                                        state_guard.names.insert(addr, name.clone());
+                            let message = crate::ownership::name_message(addr, &name, &session);
+                            if let Err(e) = crate::ownership::verify(addr, &message, &signature) {
+                                tracing::warn!("❌ Rejected name for {:?}: {}", addr, e);
+                                let msg = reject(
+                                    format!("Invalid signature: {}", e),
+                                    NameErrorReason::InvalidSignature,
+                                );
+                                let _ = client_tx.send(msg).await;
+                                continue;
+                            }
+
+                            let checked = {
+                                let mut state_guard = state_clone.write().await;
+                                let checked = name_rules.check(addr, &name, &state_guard.names);
+                                if let Ok(name) = &checked {
+                                    state_guard.names.insert(addr, name.clone());
+                                    if let Err(e) = state_guard.store.set_name(addr, name) {
+                                        tracing::error!(
+                                            "Failed to persist name for {:?}: {}",
+                                            addr,
+                                            e
+                                        );
                                     }
+                                }
+                                checked
+                            };
 
+                            match checked {
+                                Ok(name) => {
+                                    tracing::info!("Setting name: {:?} → {}", addr, name);
                                     let msg = ServerMessage::NameSet {
                                         address: format!("{:?}", addr),
                                         name,
                                     };
                                     let _ = broadcast_tx.send(msg);
                                 }
-                                Err(e) => {
-                                    tracing::error!("Failed to parse address '{}': {}", address, e);
+                                Err(rejection) => {
+                                    tracing::info!(
+                                        "❌ Rejected name '{}' for {:?}: {}",
+                                        name,
+                                        addr,
+                                        rejection.message
+                                    );
+                                    let msg = reject(rejection.message, rejection.reason);
+                                    let _ = client_tx.send(msg).await;
                                 }
                             }
                         }
@@ -167,31 +269,77 @@ where
                                 &raw_tx[..20.min(raw_tx.len())]
                             );
 
-                            match raw_tx.parse::<Bytes>() {
-                                Ok(bytes) => match provider.send_raw_transaction(&bytes).await {
-                                    Ok(pending_tx) => {
-                                        let tx_hash = *pending_tx.tx_hash();
-                                        tracing::info!("📤 Raw tx submitted: {:?}", tx_hash);
+                            let bytes = match raw_tx.parse::<Bytes>() {
+                                Ok(bytes) => bytes,
+                                Err(e) => {
+                                    METRICS.raw_txs_rejected.inc();
+                                    let error_msg = format!("Failed to parse transaction: {}", e);
+                                    tracing::error!("{}", error_msg);
 
-                                        let msg = ServerMessage::TxSubmitted {
-                                            tx_hash: format!("{:?}", tx_hash),
-                                        };
-                                        let _ = client_tx.send(msg).await;
-                                    }
-                                    Err(e) => {
-                                        let error_msg =
-                                            format!("Failed to submit transaction: {}", e);
-                                        tracing::error!("{}", error_msg);
+                                    let msg = ServerMessage::TxError {
+                                        error: error_msg,
+                                        reason: TxErrorReason::Malformed,
+                                    };
+                                    let _ = client_tx.send(msg).await;
+                                    continue;
+                                }
+                            };
 
-                                        let msg = ServerMessage::TxError { error: error_msg };
-                                        let _ = client_tx.send(msg).await;
-                                    }
-                                },
+                            match crate::preflight::check_raw_tx(
+                                &provider,
+                                &bytes,
+                                contract_address,
+                                chain_id,
+                            )
+                            .await
+                            {
+                                Ok(checked) => tracing::debug!(
+                                    "Raw tx passed pre-flight: {} from {:?}",
+                                    checked.method,
+                                    checked.signer
+                                ),
+                                Err(rejection) => {
+                                    METRICS.raw_txs_rejected.inc();
+                                    tracing::warn!(
+                                        "🚫 Rejected raw tx ({:?}): {}",
+                                        rejection.reason,
+                                        rejection.message
+                                    );
+                                    let msg = ServerMessage::TxError {
+                                        error: rejection.message,
+                                        reason: rejection.reason,
+                                    };
+                                    let _ = client_tx.send(msg).await;
+                                    continue;
+                                }
+                            }
+
+                            match provider.send_raw_transaction(&bytes).await {
+                                Ok(pending_tx) => {
+                                    METRICS.raw_txs_relayed.inc();
+                                    let tx_hash = *pending_tx.tx_hash();
+                                    tracing::info!("📤 Raw tx submitted: {:?}", tx_hash);
+
+                                    let msg = ServerMessage::TxSubmitted {
+                                        tx_hash: format!("{:?}", tx_hash),
+                                    };
+                                    let _ = client_tx.send(msg).await;
+
+                                    tokio::spawn(crate::tx_watch::watch_client_tx(
+                                        provider.clone(),
+                                        tx_hash,
+                                        client_tx.clone(),
+                                    ));
+                                }
                                 Err(e) => {
-                                    let error_msg = format!("Failed to parse transaction: {}", e);
+                                    METRICS.raw_txs_rejected.inc();
+                                    let error_msg = format!("Failed to submit transaction: {}", e);
                                     tracing::error!("{}", error_msg);
 
-                                    let msg = ServerMessage::TxError { error: error_msg };
+                                    let msg = ServerMessage::TxError {
+                                        error: error_msg,
+                                        reason: TxErrorReason::Rejected,
+                                    };
                                     let _ = client_tx.send(msg).await;
                                 }
                             }
@@ -209,17 +357,163 @@ where
                                             nonce,
                                         };
                                         let _ = client_tx.send(msg).await;
-                                        let _ = backend_tx_sender
-                                            .send(BackendTxEvent::Fund(addr, client_tx.clone()))
-                                            .await;
                                     }
                                     Err(e) => {
                                         let error_msg = format!("Failed to get nonce: {}", e);
                                         tracing::error!("{}", error_msg);
 
-                                        let msg = ServerMessage::TxError { error: error_msg };
+                                        let msg = ServerMessage::TxError {
+                                            error: error_msg,
+                                            reason: TxErrorReason::Rpc,
+                                        };
+                                        let _ = client_tx.send(msg).await;
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                tracing::error!("Failed to parse address '{}': {}", address, e);
+                            }
+                        },
+                        ClientMessage::RequestFunds { address, signature } => {
+                            let addr = match address.parse::<Address>() {
+                                Ok(addr) => addr,
+                                Err(e) => {
+                                    tracing::error!("Failed to parse address '{}': {}", address, e);
+                                    continue;
+                                }
+                            };
+
+                            let message = crate::ownership::funding_message(addr, &session);
+                            if let Err(e) = crate::ownership::verify(addr, &message, &signature) {
+                                tracing::warn!(
+                                    "🚫 Invalid ownership proof for {:?} from {}: {}",
+                                    addr,
+                                    client.ip,
+                                    e
+                                );
+                                let msg = ServerMessage::FundError {
+                                    address: format!("{:?}", addr),
+                                    error: format!("Invalid ownership proof: {}", e),
+                                    reason: FundErrorReason::InvalidSignature,
+                                };
+                                let _ = client_tx.send(msg).await;
+                                continue;
+                            }
+
+                            if fund_requests >= backend.faucet.per_connection() {
+                                let msg = ServerMessage::FundError {
+                                    address: format!("{:?}", addr),
+                                    error: "Too many funding requests on this connection"
+                                        .to_string(),
+                                    reason: FundErrorReason::ConnectionLimit,
+                                };
+                                let _ = client_tx.send(msg).await;
+                                continue;
+                            }
+                            fund_requests += 1;
+
+                            let difficulty = backend.faucet.pow_difficulty();
+                            if difficulty == 0 {
+                                queue_funding(&backend_tx_sender, addr, client.ip, &client_tx)
+                                    .await;
+                            } else {
+                                let challenge = B256::from(rand::random::<[u8; 32]>());
+                                pending_challenge = Some((addr, challenge));
+                                let msg = ServerMessage::FundChallenge {
+                                    address: format!("{:?}", addr),
+                                    challenge: format!("{:?}", challenge),
+                                    difficulty,
+                                };
+                                let _ = client_tx.send(msg).await;
+                            }
+                        }
+                        ClientMessage::SolveChallenge { address, solution } => {
+                            match address.parse::<Address>() {
+                                Ok(addr) => {
+                                    let solved = pending_challenge.take().is_some_and(
+                                        |(expected, challenge)| {
+                                            expected == addr
+                                                && crate::faucet::verify_pow(
+                                                    challenge,
+                                                    addr,
+                                                    solution,
+                                                    backend.faucet.pow_difficulty(),
+                                                )
+                                        },
+                                    );
+                                    if solved {
+                                        queue_funding(
+                                            &backend_tx_sender,
+                                            addr,
+                                            client.ip,
+                                            &client_tx,
+                                        )
+                                        .await;
+                                    } else {
+                                        tracing::warn!(
+                                            "🚫 Invalid funding challenge solution for {:?} from {}",
+                                            addr,
+                                            client.ip
+                                        );
+                                        let msg = ServerMessage::FundError {
+                                            address: format!("{:?}", addr),
+                                            error: "Challenge solution is invalid or expired"
+                                                .to_string(),
+                                            reason: FundErrorReason::ChallengeFailed,
+                                        };
+                                        let _ = client_tx.send(msg).await;
+                                    }
+                                }
+                                Err(e) => {
+                                    tracing::error!("Failed to parse address '{}': {}", address, e);
+                                }
+                            }
+                        }
+                        ClientMessage::GetPriceHistory {
+                            from_block,
+                            to_block,
+                        } => {
+                            let prices = state_clone
+                                .read()
+                                .await
+                                .store
+                                .load_prices(from_block, to_block);
+                            match prices {
+                                Ok(prices) => {
+                                    let points = prices
+                                        .into_iter()
+                                        .map(|(block_number, price)| PricePoint {
+                                            block_number,
+                                            price,
+                                        })
+                                        .collect();
+                                    let _ = client_tx
+                                        .send(ServerMessage::PriceHistory { points })
+                                        .await;
+                                }
+                                Err(e) => tracing::error!("Failed to load price history: {}", e),
+                            }
+                        }
+                        ClientMessage::GetTrades { address } => match address.parse::<Address>() {
+                            Ok(addr) => {
+                                let history =
+                                    crate::trades::current_game(&*state_clone.read().await, addr);
+                                match history {
+                                    Ok((trades, pnl)) => {
+                                        let msg = ServerMessage::Trades {
+                                            address: format!("{:?}", addr),
+                                            trades,
+                                            pnl,
+                                        };
                                         let _ = client_tx.send(msg).await;
                                     }
+                                    Err(e) => {
+                                        tracing::error!(
+                                            "Failed to load trades for {:?}: {}",
+                                            addr,
+                                            e
+                                        );
+                                    }
                                 }
                             }
                             Err(e) => {
@@ -227,16 +521,35 @@ where
                             }
                         },
                         ClientMessage::RestartGame => {
+                            {
+                                let state_guard = state_clone.read().await;
+                                crate::admin::audit(
+                                    &state_guard.store,
+                                    client.peer,
+                                    "restart_game",
+                                    client.is_admin,
+                                );
+                            }
+                            if !client.is_admin {
+                                let msg = ServerMessage::Unauthorized {
+                                    action: "restart_game".to_string(),
+                                    error: "Admin token required to restart the game".to_string(),
+                                };
+                                let _ = client_tx.send(msg).await;
+                                continue;
+                            }
+
                             tracing::info!("🔄 Restart game request received");
-                            let provider_clone = provider.clone();
+                            let chain_clone = chain.clone();
                             let state_clone = state_clone.clone();
                             let broadcast_tx_clone = broadcast_tx.clone();
-                            tokio::spawn(async move {
+                            let backend_clone = backend.clone();
+                            tasks.spawn(async move {
                                 if let Err(e) = crate::backend::handle_restart_game(
-                                    provider_clone,
-                                    contract_address,
+                                    chain_clone,
                                     state_clone,
                                     broadcast_tx_clone,
+                                    backend_clone,
                                 )
                                 .await
                                 {
@@ -259,6 +572,29 @@ where
         }
     }
 
-    send_task.abort();
+    if shutdown.is_cancelled() {
+        // Let it send the close frame.
+        let _ = send_task.await;
+    } else {
+        send_task.abort();
+    }
     Ok(())
 }
+
+async fn queue_funding(
+    backend_tx_sender: &mpsc::Sender<BackendTxEvent>,
+    addr: Address,
+    ip: IpAddr,
+    client_tx: &mpsc::Sender<ServerMessage>,
+) {
+    if backend_tx_sender
+        .send(BackendTxEvent::Fund(addr, ip, client_tx.clone()))
+        .await
+        .is_ok()
+    {
+        let msg = ServerMessage::FundQueued {
+            address: format!("{:?}", addr),
+        };
+        let _ = client_tx.send(msg).await;
+    }
+}
@@ -0,0 +1,411 @@
+//! End-to-end test against a local anvil node: deploys StockMarket, runs the
+//! whole server in-process and plays a game over `/ws`.
+//!
+//! Needs `anvil` on PATH (or `ANVIL_BIN`) and a `forge build` artifact with
+//! bytecode at `contract/out/StockMarket.sol/StockMarket.json`. It is
+//! `#[ignore]`d, so run it with `cargo test -- --ignored`; it fails if
+//! either is missing.
+
+use alloy::{
+    eips::eip2718::Encodable2718,
+    network::{EthereumWallet, TransactionBuilder},
+    primitives::{Address, Bytes, U256},
+    providers::{Provider, ProviderBuilder, WsConnect},
+    rpc::types::TransactionRequest,
+    signers::{Signer, local::PrivateKeySigner},
+    sol,
+    sol_types::SolCall,
+};
+use futures_util::{SinkExt, StreamExt};
+use monomarket::config::Config;
+use serde_json::{Value, json};
+use std::{
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async,
+    tungstenite::{Message, client::IntoClientRequest, protocol::frame::coding::CloseCode},
+};
+
+sol! {
+    #[sol(rpc)]
+    interface IStockMarket {
+        function start(uint256 length) external;
+        function register() external;
+        function buy(uint256 amount) external;
+        function sell(uint256 amount) external;
+    }
+}
+
+// anvil's first default account, which deploys and owns the contract.
+const OWNER_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+const ADMIN_TOKEN: &str = "integration-test-admin-token";
+const TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_FEE: u128 = 100_000_000_000;
+const PRIORITY_FEE: u128 = 1_000_000_000;
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Kills the node when the test ends, however it ends.
+struct Anvil(Child);
+
+impl Drop for Anvil {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn contract_bytecode() -> Option<Bytes> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("contract/out/StockMarket.sol/StockMarket.json");
+    let artifact: Value = serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+    let bytecode: Bytes = artifact["bytecode"]["object"].as_str()?.parse().ok()?;
+    (!bytecode.is_empty()).then_some(bytecode)
+}
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn spawn_anvil(port: u16) -> Option<Anvil> {
+    let bin = std::env::var("ANVIL_BIN").unwrap_or_else(|_| "anvil".to_string());
+    Command::new(bin)
+        .args(["--port", &port.to_string(), "--block-time", "1"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()
+        .map(Anvil)
+}
+
+/// Reads messages until one matches `pred`, panicking after `TIMEOUT`.
+async fn expect(ws: &mut Client, what: &str, pred: impl Fn(&Value) -> bool) -> Value {
+    let wait = async {
+        while let Some(msg) = ws.next().await {
+            if let Message::Text(text) = msg.expect("websocket error") {
+                let value: Value = serde_json::from_str(&text).expect("invalid server message");
+                if pred(&value) {
+                    return value;
+                }
+            }
+        }
+        panic!("connection closed while waiting for {}", what);
+    };
+    tokio::time::timeout(TIMEOUT, wait)
+        .await
+        .unwrap_or_else(|_| panic!("timed out waiting for {}", what))
+}
+
+fn is_type(value: &Value, ty: &str) -> bool {
+    value["type"] == ty
+}
+
+fn is_for(value: &Value, address: Address) -> bool {
+    value["address"]
+        .as_str()
+        .is_some_and(|a| a.eq_ignore_ascii_case(&address.to_string()))
+}
+
+async fn send(ws: &mut Client, msg: Value) {
+    ws.send(Message::Text(msg.to_string())).await.unwrap();
+}
+
+async fn connect(addr: std::net::SocketAddr, admin: bool) -> Client {
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    if admin {
+        request.headers_mut().insert(
+            "authorization",
+            format!("Bearer {}", ADMIN_TOKEN).parse().unwrap(),
+        );
+    }
+    connect_async(request).await.unwrap().0
+}
+
+struct Player {
+    signer: PrivateKeySigner,
+    wallet: EthereumWallet,
+    nonce: u64,
+}
+
+impl Player {
+    fn address(&self) -> Address {
+        self.signer.address()
+    }
+
+    /// Signs `call` locally, relays it through `raw_tx` and waits until the
+    /// server reports it mined.
+    async fn call(
+        &mut self,
+        ws: &mut Client,
+        contract: Address,
+        chain_id: u64,
+        gas: u64,
+        call: impl SolCall,
+    ) {
+        let envelope = TransactionRequest::default()
+            .with_to(contract)
+            .with_input(call.abi_encode())
+            .with_nonce(self.nonce)
+            .with_chain_id(chain_id)
+            .with_gas_limit(gas)
+            .with_max_fee_per_gas(MAX_FEE)
+            .with_max_priority_fee_per_gas(PRIORITY_FEE)
+            .build(&self.wallet)
+            .await
+            .unwrap();
+        self.nonce += 1;
+        let tx_hash = format!("{:?}", envelope.tx_hash());
+        let raw_tx = format!("0x{}", alloy::hex::encode(envelope.encoded_2718()));
+        send(ws, json!({ "type": "raw_tx", "raw_tx": raw_tx })).await;
+        let confirmed = expect(ws, "tx_confirmed", |v| {
+            (is_type(v, "tx_confirmed") || is_type(v, "tx_error") || is_type(v, "tx_reverted"))
+                && (v["tx_hash"] == tx_hash.as_str() || is_type(v, "tx_error"))
+        })
+        .await;
+        assert!(is_type(&confirmed, "tx_confirmed"), "{}", confirmed);
+    }
+}
+
+// Needs foundry, so it only runs when asked for: `make test` or
+// `cargo test -- --ignored`.
+#[tokio::test]
+#[ignore = "needs anvil and a forge-built contract, run with --ignored"]
+async fn full_game_against_anvil() {
+    let bytecode =
+        contract_bytecode().expect("no bytecode in the StockMarket artifact, run `forge build`");
+    let anvil_port = free_port();
+    let _anvil =
+        spawn_anvil(anvil_port).expect("anvil not found, install foundry or set ANVIL_BIN");
+    let rpc_url = format!("ws://127.0.0.1:{}", anvil_port);
+
+    let owner: PrivateKeySigner = OWNER_KEY.parse().unwrap();
+    let mut provider = None;
+    for _ in 0..50 {
+        match ProviderBuilder::new()
+            .with_recommended_fillers()
+            .wallet(EthereumWallet::from(owner.clone()))
+            .on_ws(WsConnect::new(&rpc_url))
+            .await
+        {
+            Ok(p) => {
+                provider = Some(p);
+                break;
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+        }
+    }
+    let provider = provider.expect("anvil did not come up");
+    let chain_id = provider.get_chain_id().await.unwrap();
+
+    let contract_address = provider
+        .send_transaction(TransactionRequest::default().with_deploy_code(bytecode))
+        .await
+        .unwrap()
+        .get_receipt()
+        .await
+        .unwrap()
+        .contract_address
+        .expect("deployment has no contract address");
+    IStockMarket::new(contract_address, &provider)
+        .start(U256::from(30))
+        .send()
+        .await
+        .unwrap()
+        .get_receipt()
+        .await
+        .unwrap();
+
+    let state_db = std::env::temp_dir().join(format!("monomarket-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&state_db);
+    let mut config = Config {
+        rpc_url,
+        contract_address,
+        private_key: OWNER_KEY.to_string(),
+        state_db: state_db.clone(),
+        ..Config::default()
+    };
+    config.game.duration = 30;
+    config.admin.token = Some(ADMIN_TOKEN.to_string());
+    config.validate().unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(monomarket::run(Arc::new(config), listener, async {
+        let _ = stop_rx.await;
+    }));
+
+    let signer = PrivateKeySigner::random();
+    let mut player = Player {
+        wallet: EthereumWallet::from(signer.clone()),
+        signer,
+        nonce: 0,
+    };
+    let address = player.address();
+    let checksummed = address.to_checksum(None);
+    let mut ws = connect(addr, false).await;
+
+    // Fund
+    let info = expect(&mut ws, "connection_info", |v| {
+        is_type(v, "connection_info")
+    })
+    .await;
+    let session = info["session"].as_str().unwrap().to_string();
+    let gas = |method: &str| info["gas_costs"][method].as_u64().unwrap();
+    let message = format!(
+        "Monomarket: request funds for {}\nSession: {}",
+        checksummed, session
+    );
+    let signature = player
+        .signer
+        .sign_message(message.as_bytes())
+        .await
+        .unwrap();
+    send(
+        &mut ws,
+        json!({
+            "type": "request_funds",
+            "address": checksummed,
+            "signature": signature.to_string(),
+        }),
+    )
+    .await;
+    let funded = expect(&mut ws, "funded", |v| {
+        (is_type(v, "funded") || is_type(v, "fund_error")) && is_for(v, address)
+    })
+    .await;
+    assert!(is_type(&funded, "funded"), "{}", funded);
+
+    // Register, with a signed name so restart tops the player up again
+    let name = "tester";
+    let message = format!(
+        "Monomarket: set name for {} to \"{}\"\nSession: {}",
+        checksummed, name, session
+    );
+    let signature = player
+        .signer
+        .sign_message(message.as_bytes())
+        .await
+        .unwrap();
+    send(
+        &mut ws,
+        json!({
+            "type": "set_name",
+            "name": name,
+            "address": checksummed,
+            "signature": signature.to_string(),
+        }),
+    )
+    .await;
+    expect(&mut ws, "name_set", |v| {
+        is_type(v, "name_set") && is_for(v, address)
+    })
+    .await;
+    player
+        .call(
+            &mut ws,
+            contract_address,
+            chain_id,
+            gas("register"),
+            IStockMarket::registerCall {},
+        )
+        .await;
+
+    // Buy and sell
+    player
+        .call(
+            &mut ws,
+            contract_address,
+            chain_id,
+            gas("buy"),
+            IStockMarket::buyCall {
+                amount: U256::from(2),
+            },
+        )
+        .await;
+    expect(&mut ws, "position after buy", |v| {
+        is_type(v, "position") && is_for(v, address) && v["holdings"] == 12
+    })
+    .await;
+    player
+        .call(
+            &mut ws,
+            contract_address,
+            chain_id,
+            gas("sell"),
+            IStockMarket::sellCall {
+                amount: U256::from(1),
+            },
+        )
+        .await;
+    expect(&mut ws, "position after sell", |v| {
+        is_type(v, "position") && is_for(v, address) && v["holdings"] == 11
+    })
+    .await;
+    send(
+        &mut ws,
+        json!({ "type": "get_trades", "address": checksummed }),
+    )
+    .await;
+    let trades = expect(&mut ws, "trades", |v| is_type(v, "trades")).await;
+    assert_eq!(trades["trades"].as_array().unwrap().len(), 2, "{}", trades);
+
+    // Tick
+    expect(&mut ws, "price_update", |v| is_type(v, "price_update")).await;
+
+    // Game over
+    expect(&mut ws, "game_ended", |v| is_type(v, "game_ended")).await;
+    let standings = expect(&mut ws, "final_standings", |v| {
+        is_type(v, "final_standings")
+    })
+    .await;
+    assert!(
+        standings["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|entry| is_for(entry, address)),
+        "{}",
+        standings
+    );
+
+    // Restart: refused without the token, accepted with it
+    send(&mut ws, json!({ "type": "restart_game" })).await;
+    expect(&mut ws, "unauthorized", |v| is_type(v, "unauthorized")).await;
+    let mut admin = connect(addr, true).await;
+    send(&mut admin, json!({ "type": "restart_game" })).await;
+    expect(&mut ws, "reset position", |v| {
+        is_type(v, "position") && is_for(v, address) && v["holdings"] == 10 && v["balance"] == 500
+    })
+    .await;
+    expect(&mut ws, "game_started", |v| is_type(v, "game_started")).await;
+
+    // Graceful shutdown closes the websocket with "going away"
+    stop_tx.send(()).unwrap();
+    let close = tokio::time::timeout(TIMEOUT, async {
+        while let Some(Ok(msg)) = ws.next().await {
+            if let Message::Close(frame) = msg {
+                return frame;
+            }
+        }
+        None
+    })
+    .await
+    .expect("timed out waiting for the close frame");
+    assert_eq!(close.map(|frame| frame.code), Some(CloseCode::Away));
+    tokio::time::timeout(TIMEOUT, server)
+        .await
+        .expect("server did not shut down")
+        .unwrap()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&state_db);
+}